@@ -4,18 +4,28 @@ use std::time::Duration;
 use rumqttc::{Client, Connection, QoS};
 
 use crate::format;
+use crate::mqtt::subscription::SubscriptionTracker;
 use crate::mqtt::Payload;
 
-pub fn clean_retained(mut client: Client, mut connection: Connection, dry_run: bool) {
+pub fn clean_retained(
+    mut client: Client,
+    mut connection: Connection,
+    mut subscriptions: SubscriptionTracker,
+    dry_run: bool,
+) -> anyhow::Result<()> {
     let mut amount: usize = 0;
     for notification in connection.iter() {
-        if let rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) =
-            notification.expect("connection error")
-        {
+        let notification = notification.expect("connection error");
+        subscriptions.on_event(&notification);
+        if let rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) = notification {
             break;
         }
     }
     for notification in connection.iter() {
+        if let Ok(event) = &notification {
+            subscriptions.on_event(event);
+            subscriptions.ensure_none_rejected()?;
+        }
         match notification {
             Ok(rumqttc::Event::Outgoing(rumqttc::Outgoing::Disconnect)) => break,
             Ok(rumqttc::Event::Outgoing(rumqttc::Outgoing::PingReq)) => {
@@ -54,4 +64,5 @@ pub fn clean_retained(mut client: Client, mut connection: Connection, dry_run: b
     } else {
         println!("Cleaned {amount} topics");
     }
+    Ok(())
 }
@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use chrono::{DateTime, Local};
+use tui::backend::Backend;
+use tui::layout::{Constraint, Rect};
+use tui::widgets::{Block, Borders, Row, Table};
+use tui::Frame;
+
+use crate::cli::{AlertCondition, AlertRule};
+use crate::interactive::details::parse_y;
+use crate::interactive::mqtt_history::{HistoryView, STYLE_DARKGRAY};
+use crate::interactive::ui::{split_area_vertically, STYLE_ALERT};
+use crate::interactive::watchlist::format_age;
+use crate::mqtt::topic::TopicSet;
+use crate::mqtt::Payload;
+
+/// Rules to alert on and how
+#[derive(Debug, Clone, Default)]
+pub struct Alerting {
+    pub rules: Vec<AlertRule>,
+    /// Ring the terminal bell when an alert starts
+    pub bell: bool,
+}
+
+/// Topic currently fulfilling an [`AlertRule`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub topic: String,
+    pub rule: String,
+    pub since: DateTime<Local>,
+}
+
+/// Whether the payload fulfills the condition. Silence is not a condition on the payload.
+pub fn is_fulfilled(condition: &AlertCondition, payload: &Payload) -> bool {
+    match condition {
+        AlertCondition::Above(threshold) => {
+            parse_y(payload, &[]).map_or(false, |value| value > *threshold)
+        }
+        AlertCondition::Below(threshold) => {
+            parse_y(payload, &[]).map_or(false, |value| value < *threshold)
+        }
+        AlertCondition::Equals(expected) => payload_text(payload).as_deref() == Some(expected),
+        AlertCondition::NotEquals(expected) => payload_text(payload).as_deref() != Some(expected),
+        AlertCondition::Silent(_) => false,
+    }
+}
+
+/// JSON strings are compared without their quotes
+fn payload_text(payload: &Payload) -> Option<String> {
+    match payload {
+        Payload::NotUtf8(_) => None,
+        Payload::String(str) => Some(str.to_string()),
+        Payload::Json(json) => Some(
+            json.as_str()
+                .map_or_else(|| json.dump(), ToString::to_string),
+        ),
+    }
+}
+
+/// The bell is only a nice to have, the alert is visible anyway
+pub fn ring_bell() {
+    let mut stdout = std::io::stdout();
+    _ = stdout.write_all(b"\x07").and_then(|()| stdout.flush());
+}
+
+/// Lists the active alerts below the topic overview
+#[derive(Default)]
+pub struct AlertsPanel {
+    bell: bool,
+    /// Alerts of the last check to notice new ones
+    known: HashSet<(String, String)>,
+}
+
+impl AlertsPanel {
+    pub fn new(alerting: &Alerting) -> Self {
+        Self {
+            bell: alerting.bell,
+            ..Self::default()
+        }
+    }
+
+    /// Remember the active alerts. Returns whether the bell should ring as an alert started since the last check.
+    pub fn check(&mut self, history: &HistoryView, now: DateTime<Local>) -> bool {
+        let current = history
+            .get_alerts(now)
+            .into_iter()
+            .map(|alert| (alert.topic, alert.rule))
+            .collect::<HashSet<_>>();
+        let has_new = current.iter().any(|alert| !self.known.contains(alert));
+        self.known = current;
+        self.bell && has_new
+    }
+
+    /// Draw at the bottom of the area when there are alerts.
+    /// Returns the area left above and the alerting topics.
+    pub fn draw<B>(f: &mut Frame<B>, area: Rect, history: &HistoryView) -> (Rect, TopicSet)
+    where
+        B: Backend,
+    {
+        let now = Local::now();
+        let alerts = history.get_alerts(now);
+        let alerting = TopicSet::new(alerts.iter().map(|alert| alert.topic.clone()).collect());
+        if alerts.is_empty() {
+            return (area, alerting);
+        }
+
+        // Borders need 2 lines
+        let height = u16::try_from(alerts.len())
+            .unwrap_or(u16::MAX)
+            .saturating_add(2)
+            .min(area.height / 3);
+        let (remaining_area, area) = split_area_vertically(area, area.height - height);
+
+        let rows = alerts.iter().map(|alert| {
+            Row::new(vec![
+                alert.topic.clone(),
+                alert.rule.clone(),
+                format_age(alert.since, now),
+            ])
+        });
+        let table = Table::new(rows)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(STYLE_ALERT)
+                    .title(format!("Alerts ({})", alerts.len())),
+            )
+            .style(STYLE_DARKGRAY)
+            .widths(&[
+                Constraint::Percentage(45),
+                Constraint::Percentage(45),
+                Constraint::Length(4),
+            ]);
+        f.render_widget(table, area);
+        (remaining_area, alerting)
+    }
+}
+
+#[test]
+fn conditions_on_payload() {
+    let number = Payload::String("31.5".into());
+    let json = Payload::Json(json::JsonValue::String("open".into()));
+    assert!(is_fulfilled(&AlertCondition::Above(30.0), &number));
+    assert!(!is_fulfilled(&AlertCondition::Below(30.0), &number));
+    assert!(!is_fulfilled(&AlertCondition::Above(30.0), &json));
+    assert!(is_fulfilled(&AlertCondition::Equals("open".into()), &json));
+    assert!(is_fulfilled(
+        &AlertCondition::NotEquals("open".into()),
+        &number
+    ));
+    assert!(!is_fulfilled(&AlertCondition::Silent(5), &number));
+}
@@ -1,13 +1,23 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, TimeZone};
 use json::JsonValue;
 
+use crate::interactive::mqtt_history::Entries;
 use crate::json_view;
 use crate::mqtt::{HistoryEntry, Payload};
 
 #[allow(clippy::cast_precision_loss)]
-fn parse_time_to_chart_x(time: &DateTime<Local>) -> f64 {
+pub fn parse_time_to_chart_x(time: &DateTime<Local>) -> f64 {
     time.timestamp_millis() as f64
 }
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn chart_x_to_time(x: f64) -> DateTime<Local> {
+    Local
+        .timestamp_millis_opt(x as i64)
+        .single()
+        .unwrap_or_else(Local::now)
+}
+
 struct Point {
     time: DateTime<Local>,
     y: f64,
@@ -16,24 +26,7 @@ struct Point {
 impl Point {
     fn parse(entry: &HistoryEntry, json_selector: &[usize]) -> Option<Self> {
         let time = entry.time.as_optional()?;
-        let y = match &entry.payload {
-            Payload::NotUtf8(_) => None,
-            Payload::String(str) => str.parse::<f64>().ok(),
-            Payload::Json(json) => {
-                let json = json_view::get_selected_subvalue(json, json_selector).unwrap_or(json);
-                match json {
-                    JsonValue::Number(num) => Some((*num).into()),
-                    JsonValue::Boolean(true) => Some(1.0),
-                    JsonValue::Boolean(false) => Some(0.0),
-                    #[allow(clippy::cast_precision_loss)]
-                    JsonValue::Array(arr) => Some(arr.len() as f64),
-                    JsonValue::Short(str) => str.parse::<f64>().ok(),
-                    JsonValue::String(str) => str.parse::<f64>().ok(),
-                    JsonValue::Null | JsonValue::Object(_) => None,
-                }
-            }
-        }
-        .filter(|y| y.is_finite())?;
+        let y = parse_y(&entry.payload, json_selector)?;
         Some(Self { time, y })
     }
 
@@ -43,50 +36,298 @@ impl Point {
     }
 }
 
-/// Dataset of Points showable by the graph. Ensures to create a useful graph (has at least 2 points)
-pub struct GraphData {
+/// Numeric value of the payload or of the selected JSON value in it
+pub fn parse_y(payload: &Payload, json_selector: &[usize]) -> Option<f64> {
+    match payload {
+        Payload::NotUtf8(_) => None,
+        Payload::String(str) => str.parse::<f64>().ok(),
+        Payload::Json(json) => {
+            let json = json_view::get_selected_subvalue(json, json_selector).unwrap_or(json);
+            match json {
+                JsonValue::Number(num) => Some((*num).into()),
+                JsonValue::Boolean(true) => Some(1.0),
+                JsonValue::Boolean(false) => Some(0.0),
+                #[allow(clippy::cast_precision_loss)]
+                JsonValue::Array(arr) => Some(arr.len() as f64),
+                JsonValue::Short(str) => str.parse::<f64>().ok(),
+                JsonValue::String(str) => str.parse::<f64>().ok(),
+                JsonValue::Null | JsonValue::Object(_) => None,
+            }
+        }
+    }
+    .filter(|y| y.is_finite())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YAxis {
+    Left,
+    /// Second Y axis for values in a different range than the ones on the left
+    Right,
+}
+
+/// Values of a topic to be shown in the graph
+pub struct Source<'a> {
+    pub name: String,
+    pub entries: Entries<'a>,
+    pub json_selector: &'a [usize],
+    pub y_axis: YAxis,
+}
+
+pub struct Series {
+    pub name: String,
     pub data: Vec<(f64, f64)>,
-    pub first_time: DateTime<Local>,
-    pub last_time: DateTime<Local>,
+    pub y_axis: YAxis,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Bounds {
+    fn of(data: &[(f64, f64)]) -> Option<Self> {
+        data.iter().fold(None, |bounds, (_, y)| {
+            Some(
+                bounds.map_or(Self { min: *y, max: *y }, |Self { min, max }| Self {
+                    min: min.min(*y),
+                    max: max.max(*y),
+                }),
+            )
+        })
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+/// Bounds of the left and the right Y axis
+fn y_bounds(series: &[Series]) -> Option<(Bounds, Option<Bounds>)> {
+    let bounds_of = |y_axis: Option<YAxis>| {
+        series
+            .iter()
+            .filter(|series| y_axis.map_or(true, |y_axis| series.y_axis == y_axis))
+            .filter_map(|series| Bounds::of(&series.data))
+            .reduce(Bounds::merge)
+    };
+    let left = bounds_of(Some(YAxis::Left));
+    let right = bounds_of(Some(YAxis::Right));
+    match (left, right) {
+        (Some(left), right) => Some((left, right)),
+        // Without series on the left the right axis is the only one
+        (None, _) => Some((bounds_of(None)?, None)),
+    }
+}
+
+/// Series of Points showable by the graph. All series share the time axis.
+/// Ensures to create a useful graph (each series has at least 2 points)
+pub struct GraphData {
+    pub series: Vec<Series>,
     pub x_max: f64,
     pub x_min: f64,
-    pub y_max: f64,
-    pub y_min: f64,
+    pub y_left: Bounds,
+    /// Only exists when there are series on both axes
+    pub y_right: Option<Bounds>,
 }
 
 impl GraphData {
-    pub fn parse(entries: &[HistoryEntry], json_selector: &[usize]) -> Option<Self> {
-        let points = entries
+    pub fn parse(sources: &[Source]) -> Option<Self> {
+        let series = sources
             .iter()
-            .filter_map(|o| Point::parse(o, json_selector))
+            .filter_map(|source| {
+                let data = source
+                    .entries
+                    .iter()
+                    .filter_map(|o| Point::parse(o, source.json_selector))
+                    .map(|point| point.as_graph_point())
+                    .collect::<Vec<_>>();
+                (data.len() >= 2).then(|| Series {
+                    name: source.name.clone(),
+                    data,
+                    y_axis: source.y_axis,
+                })
+            })
             .collect::<Vec<_>>();
 
-        if points.len() < 2 {
-            return None;
-        }
-
-        let first_time = points.first().unwrap().time;
-        let last_time = points.last().unwrap().time;
-        let x_min = parse_time_to_chart_x(&first_time);
-        let x_max = parse_time_to_chart_x(&last_time);
-
-        let mut data = Vec::with_capacity(points.len());
-        let mut y_min = points.first().unwrap().y;
-        let mut y_max = y_min;
-        for point in points {
-            y_min = y_min.min(point.y);
-            y_max = y_max.max(point.y);
-            data.push(point.as_graph_point());
-        }
+        let x_min = series
+            .iter()
+            .map(|series| series.data.first().unwrap().0)
+            .reduce(f64::min)?;
+        let x_max = series
+            .iter()
+            .map(|series| series.data.last().unwrap().0)
+            .reduce(f64::max)?;
+        Self::with_y_bounds(series, x_min, x_max)
+    }
 
+    fn with_y_bounds(series: Vec<Series>, x_min: f64, x_max: f64) -> Option<Self> {
+        let (y_left, y_right) = y_bounds(&series)?;
         Some(Self {
-            data,
-            first_time,
-            last_time,
+            series,
+            x_max,
+            x_min,
+            y_left,
+            y_right,
+        })
+    }
+
+    /// Only keep the points between `x_min` and `x_max` and fit the Y axes to them.
+    /// Without any point in there the Y axes stay as they are.
+    pub fn window(self, x_min: f64, x_max: f64) -> Self {
+        let series = self
+            .series
+            .into_iter()
+            .map(|series| Series {
+                data: series
+                    .data
+                    .into_iter()
+                    .filter(|(x, _)| (x_min..=x_max).contains(x))
+                    .collect(),
+                ..series
+            })
+            .collect::<Vec<_>>();
+        let (y_left, y_right) = y_bounds(&series).unwrap_or((self.y_left, self.y_right));
+        Self {
+            series,
             x_max,
             x_min,
-            y_max,
-            y_min,
+            y_left,
+            y_right,
+        }
+    }
+
+    /// Points of the series as plotted on the left axis.
+    /// Values of the right axis are scaled from its bounds onto the left bounds.
+    pub fn plotted(&self, series: &Series) -> Vec<(f64, f64)> {
+        match (series.y_axis, self.y_right) {
+            (YAxis::Right, Some(right)) => {
+                let left = self.y_left;
+                let right_range = right.max - right.min;
+                series
+                    .data
+                    .iter()
+                    .map(|(x, y)| {
+                        let relative = if right_range > 0.0 {
+                            (y - right.min) / right_range
+                        } else {
+                            0.5
+                        };
+                        (*x, left.min + relative * (left.max - left.min))
+                    })
+                    .collect()
+            }
+            _ => series.data.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+fn entries_of(values: &[(i64, &str)]) -> std::collections::VecDeque<HistoryEntry> {
+    values
+        .iter()
+        .map(|(millis, payload)| {
+            let time = Local.timestamp_millis_opt(*millis).unwrap();
+            let packet = rumqttc::Publish::new("foo", rumqttc::QoS::AtMostOnce, *payload);
+            HistoryEntry::new(&packet, time)
         })
+        .collect()
+}
+
+#[cfg(test)]
+fn source(entries: &std::collections::VecDeque<HistoryEntry>, y_axis: YAxis) -> Source<'_> {
+    Source {
+        name: "foo".to_string(),
+        entries: entries.into(),
+        json_selector: &[],
+        y_axis,
     }
 }
+
+#[test]
+fn series_share_the_time_axis() {
+    let first = entries_of(&[(1000, "1"), (3000, "2")]);
+    let second = entries_of(&[(2000, "5"), (4000, "-5")]);
+    let data =
+        GraphData::parse(&[source(&first, YAxis::Left), source(&second, YAxis::Left)]).unwrap();
+    assert_eq!(data.series.len(), 2);
+    assert!((data.x_min - 1000.0).abs() < f64::EPSILON);
+    assert!((data.x_max - 4000.0).abs() < f64::EPSILON);
+    assert_eq!(
+        data.y_left,
+        Bounds {
+            min: -5.0,
+            max: 5.0
+        }
+    );
+    assert_eq!(data.y_right, None);
+}
+
+#[test]
+fn series_with_too_few_points_are_skipped() {
+    let useful = entries_of(&[(1000, "1"), (2000, "2")]);
+    let single = entries_of(&[(1000, "100"), (2000, "not a number")]);
+    let data =
+        GraphData::parse(&[source(&useful, YAxis::Left), source(&single, YAxis::Right)]).unwrap();
+    assert_eq!(data.series.len(), 1);
+    assert_eq!(data.y_right, None);
+    assert!(GraphData::parse(&[source(&single, YAxis::Left)]).is_none());
+}
+
+#[test]
+fn right_axis_is_scaled_onto_left_bounds() {
+    let left = entries_of(&[(1000, "0"), (2000, "10")]);
+    let right = entries_of(&[(1000, "1000"), (2000, "1500"), (3000, "2000")]);
+    let data =
+        GraphData::parse(&[source(&left, YAxis::Left), source(&right, YAxis::Right)]).unwrap();
+    assert_eq!(
+        data.y_right,
+        Some(Bounds {
+            min: 1000.0,
+            max: 2000.0
+        })
+    );
+    let plotted = data.plotted(&data.series[1]);
+    assert_eq!(plotted, [(1000.0, 0.0), (2000.0, 5.0), (3000.0, 10.0)]);
+}
+
+#[test]
+fn only_right_axis_is_shown_on_the_left() {
+    let right = entries_of(&[(1000, "1000"), (2000, "2000")]);
+    let data = GraphData::parse(&[source(&right, YAxis::Right)]).unwrap();
+    assert_eq!(
+        data.y_left,
+        Bounds {
+            min: 1000.0,
+            max: 2000.0
+        }
+    );
+    assert_eq!(data.y_right, None);
+    assert_eq!(data.plotted(&data.series[0]), data.series[0].data);
+}
+
+#[test]
+fn window_fits_y_axis_to_visible_points() {
+    let entries = entries_of(&[(1000, "100"), (2000, "1"), (3000, "3")]);
+    let data = GraphData::parse(&[source(&entries, YAxis::Left)])
+        .unwrap()
+        .window(1500.0, 3500.0);
+    assert_eq!(data.series[0].data, [(2000.0, 1.0), (3000.0, 3.0)]);
+    assert_eq!(data.y_left, Bounds { min: 1.0, max: 3.0 });
+    assert!((data.x_max - 3500.0).abs() < f64::EPSILON);
+
+    let empty = GraphData::parse(&[source(&entries, YAxis::Left)])
+        .unwrap()
+        .window(5000.0, 6000.0);
+    assert!(empty.series[0].data.is_empty());
+    assert_eq!(
+        empty.y_left,
+        Bounds {
+            min: 1.0,
+            max: 100.0
+        }
+    );
+}
@@ -0,0 +1,270 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeWindow {
+    #[default]
+    All,
+    Minute,
+    TenMinutes,
+    Hour,
+}
+
+impl TimeWindow {
+    const fn next(self) -> Self {
+        match self {
+            Self::All => Self::Minute,
+            Self::Minute => Self::TenMinutes,
+            Self::TenMinutes => Self::Hour,
+            Self::Hour => Self::All,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Minute => "last minute",
+            Self::TenMinutes => "last 10 minutes",
+            Self::Hour => "last hour",
+        }
+    }
+
+    fn millis(self) -> Option<f64> {
+        let minutes = match self {
+            Self::All => return None,
+            Self::Minute => 1.0,
+            Self::TenMinutes => 10.0,
+            Self::Hour => 60.0,
+        };
+        Some(minutes * 60_000.0)
+    }
+}
+
+const MAX_ZOOM: i32 = 16;
+
+/// Part of the time axis shown by the graph and the point the cursor is on
+#[derive(Default)]
+pub struct GraphView {
+    window: TimeWindow,
+    /// Each level halves the visible span of the window
+    zoom: i32,
+    /// Milliseconds the visible span ends before the end of the time axis. Zero follows new points.
+    pan: f64,
+    /// End of the time axis while panned so it stays in place when new points arrive
+    anchor: Option<f64>,
+    /// Chart x of the point the cursor is on
+    cursor: Option<f64>,
+    /// Time range of all points of the last drawn graph. None when no graph was drawn.
+    last_range: Option<(f64, f64)>,
+    /// Chart x of the visible points of all series of the last drawn graph, sorted
+    last_points: Vec<f64>,
+}
+
+impl GraphView {
+    /// Was there a graph to interact with on the last draw?
+    pub const fn is_shown(&self) -> bool {
+        self.last_range.is_some()
+    }
+
+    pub const fn get_window(&self) -> TimeWindow {
+        self.window
+    }
+
+    pub const fn get_zoom(&self) -> i32 {
+        self.zoom
+    }
+
+    pub const fn get_cursor(&self) -> Option<f64> {
+        self.cursor
+    }
+
+    pub fn remember_drawn(&mut self, range: Option<(f64, f64)>, points: Vec<f64>) {
+        self.last_range = range;
+        self.last_points = points;
+    }
+
+    /// Time axis when all points are between `x_min` and `x_max`.
+    /// While following new points time windows end `now` so they move on even when nothing arrives.
+    pub fn range(&self, x_min: f64, x_max: f64, now: f64) -> (f64, f64) {
+        let end = self.anchor.unwrap_or_else(|| {
+            if self.window.millis().is_some() {
+                x_max.max(now)
+            } else {
+                x_max
+            }
+        });
+        (x_min.min(end), end)
+    }
+
+    fn span(&self, x_min: f64, x_max: f64) -> f64 {
+        let base = self.window.millis().unwrap_or(x_max - x_min);
+        base / 2_f64.powi(self.zoom)
+    }
+
+    fn max_pan(&self, x_min: f64, x_max: f64) -> f64 {
+        (x_max - x_min - self.span(x_min, x_max)).max(0.0)
+    }
+
+    /// Visible part of the time axis between `x_min` and `x_max`, see [`Self::range`]
+    pub fn visible(&self, x_min: f64, x_max: f64) -> (f64, f64) {
+        let span = self.span(x_min, x_max);
+        let end = x_max - self.pan.min(self.max_pan(x_min, x_max));
+        (end - span, end)
+    }
+
+    fn set_pan(&mut self, pan: f64) {
+        self.pan = self.last_range.map_or(0.0, |(x_min, x_max)| {
+            pan.clamp(0.0, self.max_pan(x_min, x_max))
+        });
+        self.anchor = if self.pan > 0.0 {
+            self.last_range.map(|(_, x_max)| x_max)
+        } else {
+            None
+        };
+    }
+
+    pub fn next_window(&mut self) {
+        self.window = self.window.next();
+        self.zoom = 0;
+        self.pan = 0.0;
+        self.anchor = None;
+    }
+
+    /// Back to the whole window following new points
+    pub fn reset(&mut self) {
+        self.zoom = 0;
+        self.pan = 0.0;
+        self.anchor = None;
+        self.cursor = None;
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.set_zoom((self.zoom + 1).min(MAX_ZOOM));
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.set_zoom((self.zoom - 1).max(0));
+    }
+
+    /// Zoom around the cursor, otherwise keep the end of the visible span
+    fn set_zoom(&mut self, zoom: i32) {
+        self.zoom = zoom;
+        if let (Some(cursor), Some((x_min, x_max))) = (self.cursor, self.last_range) {
+            let span = self.span(x_min, x_max);
+            self.set_pan(x_max - cursor - span / 2.0);
+        } else {
+            self.set_pan(self.pan);
+        }
+    }
+
+    /// Move half of the visible span back in time
+    pub fn pan_back(&mut self) {
+        if let Some((x_min, x_max)) = self.last_range {
+            self.set_pan(self.pan + self.span(x_min, x_max) / 2.0);
+        }
+    }
+
+    /// Move half of the visible span forward in time
+    pub fn pan_forward(&mut self) {
+        if let Some((x_min, x_max)) = self.last_range {
+            self.set_pan(self.pan - self.span(x_min, x_max) / 2.0);
+        }
+    }
+
+    /// Move the cursor to the previous visible point or onto the newest one when it is not on a visible point
+    pub fn cursor_previous(&mut self) {
+        let previous = self
+            .cursor
+            .filter(|cursor| self.last_points.contains(cursor))
+            .map(|cursor| {
+                let previous = self.last_points.iter().rev().find(|x| **x < cursor);
+                previous.copied().unwrap_or(cursor)
+            });
+        self.cursor = previous.or_else(|| self.last_points.last().copied());
+    }
+
+    /// Move the cursor to the next visible point or onto the newest one when it is not on a visible point
+    pub fn cursor_next(&mut self) {
+        let next = self
+            .cursor
+            .filter(|cursor| self.last_points.contains(cursor))
+            .map(|cursor| {
+                let next = self.last_points.iter().find(|x| **x > cursor);
+                next.copied().unwrap_or(cursor)
+            });
+        self.cursor = next.or_else(|| self.last_points.last().copied());
+    }
+}
+
+#[cfg(test)]
+fn drawn(points: &[f64]) -> GraphView {
+    let mut view = GraphView::default();
+    view.remember_drawn(Some((points[0], points[points.len() - 1])), points.to_vec());
+    view
+}
+
+#[test]
+fn window_ends_with_newest_point() {
+    let mut view = drawn(&[0.0, 600_000.0]);
+    assert_eq!(view.visible(0.0, 600_000.0), (0.0, 600_000.0));
+    view.next_window();
+    assert_eq!(view.get_window(), TimeWindow::Minute);
+    assert_eq!(view.visible(0.0, 600_000.0), (540_000.0, 600_000.0));
+}
+
+#[test]
+fn zoom_and_pan_stay_inside_points() {
+    let mut view = drawn(&[0.0, 1000.0]);
+    view.zoom_in();
+    assert_eq!(view.visible(0.0, 1000.0), (500.0, 1000.0));
+    view.pan_back();
+    assert_eq!(view.visible(0.0, 1000.0), (250.0, 750.0));
+    view.pan_back();
+    view.pan_back();
+    assert_eq!(view.visible(0.0, 1000.0), (0.0, 500.0));
+    view.pan_forward();
+    assert_eq!(view.visible(0.0, 1000.0), (250.0, 750.0));
+    view.reset();
+    assert_eq!(view.visible(0.0, 1000.0), (0.0, 1000.0));
+}
+
+#[test]
+fn zoom_centers_on_cursor() {
+    let mut view = drawn(&[0.0, 250.0, 500.0, 1000.0]);
+    view.cursor_previous();
+    view.cursor_previous();
+    assert_eq!(view.get_cursor(), Some(500.0));
+    view.zoom_in();
+    assert_eq!(view.visible(0.0, 1000.0), (250.0, 750.0));
+}
+
+#[test]
+fn cursor_moves_along_points() {
+    let mut view = drawn(&[0.0, 250.0, 500.0]);
+    view.cursor_next();
+    assert_eq!(view.get_cursor(), Some(500.0));
+    view.cursor_next();
+    assert_eq!(view.get_cursor(), Some(500.0));
+    view.cursor_previous();
+    view.cursor_previous();
+    view.cursor_previous();
+    assert_eq!(view.get_cursor(), Some(0.0));
+    view.cursor_next();
+    assert_eq!(view.get_cursor(), Some(250.0));
+
+    // Points scrolled out of view put the cursor back onto the newest visible one
+    view.remember_drawn(Some((0.0, 1000.0)), vec![750.0, 1000.0]);
+    view.cursor_previous();
+    assert_eq!(view.get_cursor(), Some(1000.0));
+}
+
+#[test]
+fn time_window_follows_now_until_panned() {
+    let mut view = drawn(&[0.0, 100_000.0]);
+    assert_eq!(view.range(0.0, 100_000.0, 200_000.0), (0.0, 100_000.0));
+    view.next_window();
+    assert_eq!(view.range(0.0, 100_000.0, 200_000.0), (0.0, 200_000.0));
+
+    view.remember_drawn(Some((0.0, 200_000.0)), vec![0.0, 100_000.0]);
+    view.pan_back();
+    assert_eq!(view.range(0.0, 100_000.0, 300_000.0), (0.0, 200_000.0));
+    view.reset();
+    assert_eq!(view.range(0.0, 100_000.0, 300_000.0), (0.0, 300_000.0));
+}
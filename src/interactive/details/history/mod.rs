@@ -1,45 +1,108 @@
 use std::fmt::Write;
 
+use chrono::{DateTime, Local};
 use tui::backend::Backend;
 use tui::layout::{Constraint, Rect};
 use tui::style::{Color, Modifier, Style};
-use tui::text::Span;
-use tui::widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Row, Table, TableState};
+use tui::text::{Span, Spans};
+use tui::widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph, Row, Table};
 use tui::{symbols, Frame};
 
-use crate::interactive::ui::{split_area_vertically, STYLE_BOLD};
+use crate::interactive::mqtt_history::Entries;
+use crate::interactive::ui::{focus_color, split_area_vertically, STYLE_BOLD};
 use crate::mqtt::{HistoryEntry, Payload, Time};
 use crate::{format, json_view};
-use graph_data::GraphData;
+use graph_data::{chart_x_to_time, parse_time_to_chart_x, GraphData};
+pub use graph_data::{parse_y, Source, YAxis};
+pub use graph_view::GraphView;
+use statistics::{format_number, Statistics};
+pub use table_view::{RowKey, TableView};
 
 mod graph_data;
+mod graph_view;
+mod statistics;
+mod table_view;
 
-pub fn draw<B>(
+/// Entry the payload is compared with in the diff
+const STYLE_MARKED: Style = Style {
+    fg: Some(Color::Yellow),
+    bg: None,
+    add_modifier: Modifier::ITALIC,
+    sub_modifier: Modifier::empty(),
+};
+
+/// Colours of the series in the graph, the first one is the selected topic
+const SERIES_COLORS: [Color; 6] = [
+    Color::LightGreen,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::Yellow,
+    Color::LightCyan,
+    Color::LightRed,
+];
+
+/// Draws the graph when the sources contain something to show. Returns the remaining area for the table.
+/// The time window ends at `now` which is the time of pausing while paused.
+pub fn draw_graph<B>(
     f: &mut Frame<B>,
     area: Rect,
-    topic_history: &[HistoryEntry],
-    json_selector: &[usize],
-) where
+    sources: &[Source],
+    view: &mut GraphView,
+    has_focus: bool,
+    now: DateTime<Local>,
+) -> Rect
+where
     B: Backend,
 {
-    let table_area = GraphData::parse(topic_history, json_selector).map_or(area, |data| {
+    if let Some(data) = GraphData::parse(sources) {
+        let now = parse_time_to_chart_x(&now);
+        let (x_min, x_max) = view.range(data.x_min, data.x_max, now);
+        let (start, end) = view.visible(x_min, x_max);
+        let data = data.window(start, end);
+        // The cursor steps over the points of all series
+        let mut points = data
+            .series
+            .iter()
+            .flat_map(|series| series.data.iter().map(|(x, _)| *x))
+            .collect::<Vec<_>>();
+        points.sort_by(f64::total_cmp);
+        points.dedup();
+        view.remember_drawn(Some((x_min, x_max)), points);
+
         let (table_area, graph_area) = split_area_vertically(area, area.height / 2);
-        draw_graph(f, graph_area, &data);
+        // Header, borders and a row per series
+        #[allow(clippy::cast_possible_truncation)]
+        let statistics_height = data.series.len() as u16 + 3;
+        let graph_area = if graph_area.height >= statistics_height + 8 {
+            let (graph_area, statistics_area) =
+                split_area_vertically(graph_area, graph_area.height - statistics_height);
+            draw_statistics(f, statistics_area, &data);
+            graph_area
+        } else {
+            graph_area
+        };
+        draw_chart(f, graph_area, &data, view, has_focus);
         table_area
-    });
-    draw_table(f, table_area, topic_history, json_selector);
+    } else {
+        view.remember_drawn(None, Vec::new());
+        area
+    }
 }
 
+/// Amount of entries and their rate. `across_brokers` is the amount of entries of the topic on all brokers.
 #[allow(clippy::cast_precision_loss)]
-fn draw_table<B>(
-    f: &mut Frame<B>,
-    area: Rect,
-    topic_history: &[HistoryEntry],
-    json_selector: &[usize],
-) where
-    B: Backend,
-{
+pub fn table_title(
+    topic_history: Entries,
+    dropped: usize,
+    across_brokers: Option<usize>,
+) -> String {
     let mut title = format!("History ({}", topic_history.len());
+    if dropped > 0 {
+        write!(title, ", {dropped} older dropped").expect("write to string should never fail");
+    }
+    if let Some(amount) = across_brokers {
+        write!(title, ", {amount} on all brokers").expect("write to string should never fail");
+    }
 
     let without_retain = topic_history
         .iter()
@@ -78,9 +141,24 @@ fn draw_table<B>(
         }
         .expect("write to string should never fail");
     }
-    title += ")";
+    title + ")"
+}
 
-    let rows = topic_history.iter().map(|entry| {
+/// Draws the entries with the broker column when they have brokers.
+/// The selected row has to be set in the state of the `view`.
+pub fn draw_table<B>(
+    f: &mut Frame<B>,
+    area: Rect,
+    title: String,
+    entries: &[(Option<&str>, &HistoryEntry)],
+    json_selector: &[usize],
+    view: &mut TableView,
+    has_focus: bool,
+) where
+    B: Backend,
+{
+    let marked = view.get_marked_row_to_draw();
+    let rows = entries.iter().enumerate().map(|(index, (broker, entry))| {
         let time = entry.time.to_string();
         let qos = format::qos(entry.qos).to_string();
         let value = match &entry.payload {
@@ -90,58 +168,269 @@ fn draw_table<B>(
                 .unwrap_or(json)
                 .dump(),
         };
-        Row::new(vec![time, qos, value])
+        let mut cells = vec![time, qos, value];
+        if let Some(broker) = broker {
+            cells.insert(0, (*broker).to_string());
+        }
+        let row = Row::new(cells);
+        if marked == Some(index) {
+            row.style(STYLE_MARKED)
+        } else {
+            row
+        }
     });
 
+    let mut header = vec!["Time", "QoS", "Value"];
+    let mut widths = vec![
+        Constraint::Length(12),
+        Constraint::Length(11),
+        Constraint::Percentage(100),
+    ];
+    if entries.iter().any(|(broker, _)| broker.is_some()) {
+        header.insert(0, "Broker");
+        widths.insert(0, Constraint::Percentage(25));
+    }
+
+    let focus_color = focus_color(has_focus);
+    let highlight_style = if has_focus {
+        Style::default().fg(Color::Black).bg(focus_color)
+    } else {
+        STYLE_BOLD
+    };
     let t = Table::new(rows)
-        .block(Block::default().borders(Borders::ALL).title(title))
-        .header(Row::new(vec!["Time", "QoS", "Value"]).style(STYLE_BOLD))
-        .highlight_style(STYLE_BOLD)
-        .widths(&[
-            Constraint::Length(12),
-            Constraint::Length(11),
-            Constraint::Percentage(100),
-        ]);
-
-    let mut state = TableState::default();
-    state.select(Some(topic_history.len() - 1));
-
-    f.render_stateful_widget(t, area, &mut state);
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(focus_color))
+                .title(title),
+        )
+        .header(Row::new(header).style(STYLE_BOLD))
+        .highlight_style(highlight_style)
+        .widths(&widths);
+
+    f.render_stateful_widget(t, area, &mut view.state);
+    view.last_area = area;
 }
 
-fn draw_graph<B>(f: &mut Frame<B>, area: Rect, points: &GraphData)
+/// Statistics of the visible points of each series
+fn draw_statistics<B>(f: &mut Frame<B>, area: Rect, points: &GraphData)
 where
     B: Backend,
 {
-    const STYLE: Style = Style {
-        fg: Some(Color::LightGreen),
-        bg: None,
-        add_modifier: Modifier::empty(),
-        sub_modifier: Modifier::empty(),
+    let named = points.series.len() > 1;
+    let rows = points.series.iter().enumerate().map(|(index, series)| {
+        let mut cells = Statistics::of(&series.data).map_or_else(
+            || vec![String::from("no values in the time window")],
+            |statistics| {
+                let last_change = statistics
+                    .last_change
+                    .map_or_else(String::new, |(change, x)| {
+                        let sign = if change > 0.0 { "+" } else { "" };
+                        format!(
+                            "{sign}{} at {}",
+                            format_number(change),
+                            format_cursor_time(x)
+                        )
+                    });
+                let rate = statistics
+                    .rate
+                    .map_or_else(String::new, |rate| format!("{}/s", format_number(rate)));
+                vec![
+                    format_number(statistics.min),
+                    format_number(statistics.max),
+                    format_number(statistics.mean),
+                    format_number(statistics.median),
+                    format_number(statistics.std_dev),
+                    last_change,
+                    rate,
+                ]
+            },
+        );
+        if named {
+            cells.insert(0, series.name.clone());
+        }
+        Row::new(cells).style(Style::default().fg(SERIES_COLORS[index % SERIES_COLORS.len()]))
+    });
+
+    let mut header = vec![
+        "Min",
+        "Max",
+        "Mean",
+        "Median",
+        "Std dev",
+        "Last change",
+        "Rate",
+    ];
+    let mut widths = vec![
+        Constraint::Ratio(1, 10),
+        Constraint::Ratio(1, 10),
+        Constraint::Ratio(1, 10),
+        Constraint::Ratio(1, 10),
+        Constraint::Ratio(1, 10),
+        Constraint::Ratio(3, 10),
+        Constraint::Ratio(2, 10),
+    ];
+    if named {
+        header.insert(0, "Series");
+        widths.insert(0, Constraint::Ratio(2, 10));
+    }
+
+    let table = Table::new(rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Statistics of the time window"),
+        )
+        .header(Row::new(header).style(STYLE_BOLD))
+        .widths(&widths);
+    f.render_widget(table, area);
+}
+
+fn draw_chart<B>(
+    f: &mut Frame<B>,
+    area: Rect,
+    points: &GraphData,
+    view: &GraphView,
+    has_focus: bool,
+) where
+    B: Backend,
+{
+    // The chart has only one Y axis, the labels of the second one get their own column
+    let right_labels = points.y_right.map(|right| {
+        [right.max.to_string(), right.min.to_string()]
+            .map(|label| Spans::from(Span::styled(label, Style::default().fg(Color::Gray))))
+    });
+    let right_width = right_labels.as_ref().map_or(0, |labels| {
+        #[allow(clippy::cast_possible_truncation)]
+        let width = labels.iter().map(Spans::width).max().unwrap_or(0) as u16;
+        (width + 1).min(area.width / 4)
+    });
+    let chart_area = Rect {
+        width: area.width - right_width,
+        ..area
     };
-    let datasets = vec![Dataset::default()
-        .graph_type(GraphType::Line)
-        .marker(symbols::Marker::Braille)
-        .style(STYLE)
-        .data(&points.data)];
+
+    // Only name the series when there are multiple to tell apart in the legend
+    let named = points.series.len() > 1;
+    let plotted = points
+        .series
+        .iter()
+        .map(|series| points.plotted(series))
+        .collect::<Vec<_>>();
+    let mut datasets = points
+        .series
+        .iter()
+        .zip(&plotted)
+        .enumerate()
+        .map(|(index, (series, data))| {
+            let mut dataset = Dataset::default()
+                .graph_type(GraphType::Line)
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(SERIES_COLORS[index % SERIES_COLORS.len()]))
+                .data(data);
+            if named {
+                dataset = dataset.name(match (series.y_axis, points.y_right) {
+                    (YAxis::Right, Some(_)) => format!("{} (right)", series.name),
+                    _ => series.name.clone(),
+                });
+            }
+            dataset
+        })
+        .collect::<Vec<_>>();
+
+    let mut title = graph_title(view);
+    let cursor_line = view
+        .get_cursor()
+        .filter(|cursor| (points.x_min..=points.x_max).contains(cursor))
+        .map(|cursor| {
+            title += &cursor_values(points, cursor, named);
+            [(cursor, points.y_left.min), (cursor, points.y_left.max)]
+        });
+    if let Some(cursor_line) = &cursor_line {
+        let mut dataset = Dataset::default()
+            .graph_type(GraphType::Line)
+            .marker(symbols::Marker::Braille)
+            .style(Style::default().fg(Color::White))
+            .data(cursor_line);
+        if named {
+            dataset = dataset.name("cursor");
+        }
+        datasets.push(dataset);
+    }
 
     let chart = Chart::new(datasets)
-        .block(Block::default().title("Graph").borders(Borders::ALL))
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(focus_color(has_focus))),
+        )
+        .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)))
         .x_axis(
             Axis::default()
                 .labels(vec![
-                    Span::raw(points.first_time.format("%H:%M:%S").to_string()),
-                    Span::raw(points.last_time.format("%H:%M:%S").to_string()),
+                    Span::raw(chart_x_to_time(points.x_min).format("%H:%M:%S").to_string()),
+                    Span::raw(chart_x_to_time(points.x_max).format("%H:%M:%S").to_string()),
                 ])
                 .bounds([points.x_min, points.x_max]),
         )
         .y_axis(
             Axis::default()
                 .labels(vec![
-                    Span::raw(points.y_min.to_string()),
-                    Span::raw(points.y_max.to_string()),
+                    Span::raw(points.y_left.min.to_string()),
+                    Span::raw(points.y_left.max.to_string()),
                 ])
-                .bounds([points.y_min, points.y_max]),
+                .bounds([points.y_left.min, points.y_left.max]),
         );
-    f.render_widget(chart, area);
+    f.render_widget(chart, chart_area);
+
+    if let Some([max, min]) = right_labels {
+        // Align with the top and the bottom of the plotted area (inside the border, above the X axis)
+        let label_area = Rect {
+            x: chart_area.x + chart_area.width,
+            width: right_width,
+            y: area.y + 1,
+            height: area.height.saturating_sub(4).max(2),
+        };
+        let mut lines = vec![max];
+        lines.resize(
+            usize::from(label_area.height.saturating_sub(1)),
+            Spans::default(),
+        );
+        lines.push(min);
+        f.render_widget(Paragraph::new(lines), label_area);
+    }
+}
+
+fn graph_title(view: &GraphView) -> String {
+    let mut title = format!("Graph ({}", view.get_window().name());
+    if view.get_zoom() > 0 {
+        write!(title, ", zoom {}x", 1_u32 << view.get_zoom())
+            .expect("write to string should never fail");
+    }
+    title + ")"
+}
+
+/// Value and time of the point nearest to the cursor of each series
+fn cursor_values(points: &GraphData, cursor: f64, named: bool) -> String {
+    let mut text = String::new();
+    for series in &points.series {
+        let nearest = series
+            .data
+            .iter()
+            .min_by(|(a, _), (b, _)| (a - cursor).abs().total_cmp(&(b - cursor).abs()));
+        if let Some((x, y)) = nearest {
+            text += "  ";
+            if named {
+                write!(text, "{}: ", series.name).expect("write to string should never fail");
+            }
+            write!(text, "{y} at {}", format_cursor_time(*x))
+                .expect("write to string should never fail");
+        }
+    }
+    text
+}
+
+fn format_cursor_time(x: f64) -> String {
+    chart_x_to_time(x).format("%H:%M:%S%.3f").to_string()
 }
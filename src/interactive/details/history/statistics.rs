@@ -0,0 +1,105 @@
+/// Statistics of the values of a graph series
+#[derive(Debug, PartialEq)]
+pub struct Statistics {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+    /// Difference and chart x of the newest value that differs from the one before
+    pub last_change: Option<(f64, f64)>,
+    /// Average change per second from the first to the last value
+    pub rate: Option<f64>,
+}
+
+impl Statistics {
+    /// Expects the points ordered by time like [`super::graph_data::Series::data`]
+    pub fn of(data: &[(f64, f64)]) -> Option<Self> {
+        let (first_x, first_y) = *data.first()?;
+        let (last_x, last_y) = *data.last()?;
+
+        let mut values = data.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+        values.sort_by(f64::total_cmp);
+        #[allow(clippy::cast_precision_loss)]
+        let amount = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / amount;
+        let variance = values.iter().map(|y| (y - mean).powi(2)).sum::<f64>() / amount;
+        let middle = values.len() / 2;
+        let median = if values.len() % 2 == 0 {
+            (values[middle - 1] + values[middle]) / 2.0
+        } else {
+            values[middle]
+        };
+
+        let last_change = data
+            .windows(2)
+            .rev()
+            .find(|pair| (pair[1].1 - pair[0].1).abs() > 0.0)
+            .map(|pair| (pair[1].1 - pair[0].1, pair[1].0));
+        let seconds = (last_x - first_x) / 1000.0;
+        let rate = (seconds > 0.0).then(|| (last_y - first_y) / seconds);
+
+        Some(Self {
+            min: values[0],
+            max: values[values.len() - 1],
+            mean,
+            median,
+            std_dev: variance.sqrt(),
+            last_change,
+            rate,
+        })
+    }
+}
+
+/// Up to 3 decimal places without trailing zeros
+pub fn format_number(number: f64) -> String {
+    let formatted = format!("{number:.3}");
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+    if formatted == "-0" {
+        "0".to_string()
+    } else {
+        formatted.to_string()
+    }
+}
+
+#[test]
+fn statistics_works() {
+    let statistics =
+        Statistics::of(&[(0.0, 2.0), (1000.0, 4.0), (2000.0, 4.0), (4000.0, 10.0)]).unwrap();
+    assert_eq!(
+        statistics,
+        Statistics {
+            min: 2.0,
+            max: 10.0,
+            mean: 5.0,
+            median: 4.0,
+            std_dev: 3.0,
+            last_change: Some((6.0, 4000.0)),
+            rate: Some(2.0),
+        }
+    );
+}
+
+#[test]
+fn statistics_of_constant_values() {
+    let statistics = Statistics::of(&[(0.0, 1.0), (1000.0, 1.0), (2000.0, 1.0)]).unwrap();
+    assert_eq!(statistics.last_change, None);
+    assert_eq!(statistics.rate, Some(0.0));
+    assert!(statistics.std_dev.abs() < f64::EPSILON);
+}
+
+#[test]
+fn statistics_of_single_value() {
+    let statistics = Statistics::of(&[(0.0, 3.0)]).unwrap();
+    assert!((statistics.median - 3.0).abs() < f64::EPSILON);
+    assert_eq!(statistics.rate, None);
+    assert_eq!(Statistics::of(&[]), None);
+}
+
+#[test]
+fn format_number_works() {
+    assert_eq!(format_number(21.5), "21.5");
+    assert_eq!(format_number(2.0), "2");
+    assert_eq!(format_number(-0.0001), "0");
+    assert_eq!(format_number(1.234_56), "1.235");
+}
@@ -0,0 +1,186 @@
+use chrono::{DateTime, Local};
+use tui::layout::Rect;
+use tui::widgets::TableState;
+
+use crate::interactive::ui::CursorMove;
+
+/// Identifies a row of the history table by the broker and the time its entry was received.
+/// Unlike the row index it stays the same when older entries are dropped.
+pub type RowKey<'a> = (Option<&'a str>, DateTime<Local>);
+
+/// Entry of the history table chosen by the user
+struct Selected {
+    topic: String,
+    broker: Option<String>,
+    received: DateTime<Local>,
+}
+
+impl Selected {
+    fn new(topic: &str, (broker, received): RowKey) -> Self {
+        Self {
+            topic: topic.to_string(),
+            broker: broker.map(ToString::to_string),
+            received,
+        }
+    }
+
+    fn is(&self, (broker, received): &RowKey) -> bool {
+        self.received == *received && self.broker.as_deref() == *broker
+    }
+
+    /// Row of the entry when it is still in the table of the topic
+    fn row(&self, topic: &str, rows: &[RowKey]) -> Option<usize> {
+        if self.topic == topic {
+            rows.iter().position(|row| self.is(row))
+        } else {
+            None
+        }
+    }
+}
+
+/// Selection and scroll position of the history table. Follows the newest entry unless an older one is selected.
+#[derive(Default)]
+pub struct TableView {
+    selected: Option<Selected>,
+    /// Entry the selected one is compared with in the diff
+    marked: Option<Selected>,
+    /// Row of the marked entry on the next draw
+    marked_row: Option<usize>,
+    pub state: TableState,
+    pub last_area: Rect,
+}
+
+impl TableView {
+    /// Row of the selected entry in the table with the `rows` ordered by the time they were received.
+    /// A dropped selected entry continues with the oldest one remaining.
+    pub fn selected_row(&self, topic: &str, rows: &[RowKey]) -> usize {
+        self.selected
+            .as_ref()
+            .filter(|selected| selected.topic == topic)
+            .map_or(usize::MAX, |selected| {
+                selected.row(topic, rows).unwrap_or_else(|| {
+                    rows.partition_point(|(_, received)| *received < selected.received)
+                })
+            })
+            .min(rows.len().saturating_sub(1))
+    }
+
+    /// Row of the marked entry unless it was dropped already or belongs to another topic
+    pub fn marked_row(&self, topic: &str, rows: &[RowKey]) -> Option<usize> {
+        self.marked
+            .as_ref()
+            .and_then(|marked| marked.row(topic, rows))
+    }
+
+    /// Mark the selected entry or unmark it when it is marked already
+    pub fn toggle_mark(&mut self, topic: &str, rows: &[RowKey]) {
+        let row = self.selected_row(topic, rows);
+        self.marked = if self.marked_row(topic, rows) == Some(row) {
+            None
+        } else {
+            rows.get(row).map(|key| Selected::new(topic, *key))
+        };
+    }
+
+    /// Remember the rows to highlight on the next draw. Returns the selected row.
+    pub fn prepare_draw(&mut self, topic: &str, rows: &[RowKey]) -> usize {
+        let selected = self.selected_row(topic, rows);
+        self.state.select(Some(selected));
+        self.marked_row = self.marked_row(topic, rows);
+        selected
+    }
+
+    pub const fn get_marked_row_to_draw(&self) -> Option<usize> {
+        self.marked_row
+    }
+
+    pub fn follows_newest(&self, topic: &str) -> bool {
+        self.selected
+            .as_ref()
+            .map_or(true, |selected| selected.topic != topic)
+    }
+
+    /// Selecting the newest row follows new entries again
+    pub fn change_selected(&mut self, topic: &str, rows: &[RowKey], cursor_move: CursorMove) {
+        let current = self.selected_row(topic, rows);
+        let page_jump = (self.last_area.height / 2) as usize;
+        let new_row = match cursor_move {
+            CursorMove::Absolute(index) => index,
+            CursorMove::OneUp => current.saturating_sub(1),
+            CursorMove::OneDown => current.saturating_add(1),
+            CursorMove::PageUp => current.saturating_sub(page_jump),
+            CursorMove::PageDown => current.saturating_add(page_jump),
+        }
+        .min(rows.len().saturating_sub(1));
+        self.selected = (new_row + 1 < rows.len()).then(|| Selected::new(topic, rows[new_row]));
+    }
+}
+
+#[cfg(test)]
+fn rows(broker: Option<&str>, seconds: std::ops::Range<i64>) -> Vec<RowKey<'_>> {
+    use chrono::TimeZone;
+    seconds
+        .map(|second| (broker, Local.timestamp_opt(second, 0).unwrap()))
+        .collect()
+}
+
+#[test]
+fn follows_newest_without_selection() {
+    let mut view = TableView::default();
+    assert_eq!(view.selected_row("foo", &rows(None, 0..5)), 4);
+    view.change_selected("foo", &rows(None, 0..5), CursorMove::OneUp);
+    assert_eq!(view.selected_row("foo", &rows(None, 0..5)), 3);
+    assert_eq!(view.selected_row("foo", &rows(None, 0..6)), 3);
+    assert!(!view.follows_newest("foo"));
+    view.change_selected("foo", &rows(None, 0..6), CursorMove::Absolute(usize::MAX));
+    assert!(view.follows_newest("foo"));
+    assert_eq!(view.selected_row("foo", &rows(None, 0..7)), 6);
+}
+
+#[test]
+fn selection_stays_on_entry_when_older_are_dropped() {
+    let mut view = TableView::default();
+    view.change_selected("foo", &rows(None, 0..5), CursorMove::Absolute(2));
+    assert_eq!(view.selected_row("foo", &rows(None, 0..5)), 2);
+    assert_eq!(view.selected_row("foo", &rows(None, 1..6)), 1);
+    assert_eq!(view.selected_row("foo", &rows(None, 10..15)), 0);
+}
+
+#[test]
+fn selection_stays_on_entry_of_its_broker() {
+    let mut merged = rows(Some("a"), 0..3);
+    merged.extend(rows(Some("b"), 1..4));
+    merged.sort_by_key(|(_, received)| *received);
+    let mut view = TableView::default();
+    view.change_selected("foo", &merged, CursorMove::Absolute(3));
+    assert_eq!(view.selected_row("foo", &merged), 3);
+
+    // Only the oldest entry of b is dropped
+    merged.remove(2);
+    assert_eq!(view.selected_row("foo", &merged), 2);
+
+    // A dropped selection continues with the oldest remaining entry
+    merged.remove(2);
+    assert_eq!(view.selected_row("foo", &merged), 2);
+}
+
+#[test]
+fn other_topic_shows_newest() {
+    let mut view = TableView::default();
+    view.change_selected("foo", &rows(None, 0..5), CursorMove::Absolute(0));
+    assert_eq!(view.selected_row("bar", &rows(None, 0..3)), 2);
+    assert!(view.follows_newest("bar"));
+}
+
+#[test]
+fn mark_is_toggled_on_selected_entry() {
+    let mut view = TableView::default();
+    view.change_selected("foo", &rows(None, 0..5), CursorMove::Absolute(1));
+    view.toggle_mark("foo", &rows(None, 0..5));
+    assert_eq!(view.marked_row("foo", &rows(None, 0..5)), Some(1));
+    assert_eq!(view.marked_row("foo", &rows(None, 1..6)), Some(0));
+    assert_eq!(view.marked_row("foo", &rows(None, 2..7)), None);
+    assert_eq!(view.marked_row("bar", &rows(None, 0..5)), None);
+    view.toggle_mark("foo", &rows(None, 0..5));
+    assert_eq!(view.marked_row("foo", &rows(None, 0..5)), None);
+}
@@ -1,58 +1,264 @@
 use std::cmp::min;
+use std::fmt::Write;
 
+use chrono::{DateTime, Local};
 use json::JsonValue;
 use tui::backend::Backend;
 use tui::layout::Rect;
 use tui::style::{Color, Style};
 use tui::widgets::{Block, Borders, List, ListItem};
 use tui::Frame;
-use tui_tree_widget::{Tree, TreeState};
+use tui_tree_widget::{Tree, TreeItem, TreeState};
 
+use crate::interactive::keymap::{Action, KeyMap};
+use crate::interactive::mqtt_history::HistoryView;
+use crate::interactive::ui::CursorMove;
 use crate::interactive::ui::{focus_color, get_row_inside, split_area_vertically};
-use crate::json_view::root_tree_items_from_json;
+use crate::interactive::ElementInFocus;
+use crate::json_view::{self, root_tree_items_from_json, Change};
 use crate::mqtt::{HistoryEntry, Payload};
+pub use history::parse_y;
+use history::{GraphView, RowKey, Source, TableView, YAxis};
 
 mod history;
 
+/// Series shown in the graph in addition to the one of the selected topic
+struct PinnedSeries {
+    topic: String,
+    json_selector: Vec<usize>,
+    name: String,
+    y_axis: YAxis,
+}
+
 #[derive(Default)]
 pub struct Details {
     pub json_view: TreeState,
     pub last_json_area: Option<Rect>,
+    pub graph_view: GraphView,
+    table_view: TableView,
+    pinned: Vec<PinnedSeries>,
+    /// Compare the JSON payload with the marked or the previous entry
+    diff: bool,
+    /// Key of [`Action::NextPane`] shown in the title of the JSON payload
+    next_pane_key: String,
 }
 
 impl Details {
+    pub fn new(keys: &KeyMap) -> Self {
+        Self {
+            next_pane_key: keys.hint(Action::NextPane),
+            ..Self::default()
+        }
+    }
+
+    fn pinned_index(&self, topic: &str) -> Option<usize> {
+        let json_selector = self.json_view.selected();
+        self.pinned
+            .iter()
+            .position(|pinned| pinned.topic == topic && pinned.json_selector == json_selector)
+    }
+
+    fn pin(&mut self, topic: &str, json: Option<&JsonValue>, y_axis: YAxis) {
+        let json_selector = self.json_view.selected();
+        self.pinned.push(PinnedSeries {
+            topic: topic.to_string(),
+            name: series_name(topic, json, &json_selector),
+            json_selector,
+            y_axis,
+        });
+    }
+
+    /// Pin the selected value of the topic into the graph or unpin it when it is already pinned
+    pub fn toggle_pin(&mut self, topic: &str, json: Option<&JsonValue>) {
+        if let Some(index) = self.pinned_index(topic) {
+            self.pinned.remove(index);
+        } else {
+            self.pin(topic, json, YAxis::Left);
+        }
+    }
+
+    /// Move the selected value of the topic to the other Y axis. Pins it onto the second axis when not pinned yet.
+    pub fn toggle_y_axis(&mut self, topic: &str, json: Option<&JsonValue>) {
+        if let Some(index) = self.pinned_index(topic) {
+            let pinned = &mut self.pinned[index];
+            pinned.y_axis = match pinned.y_axis {
+                YAxis::Left => YAxis::Right,
+                YAxis::Right => YAxis::Left,
+            };
+        } else {
+            self.pin(topic, json, YAxis::Right);
+        }
+    }
+
+    /// Entry of the history table shown in the payload pane. The newest one unless an older one was selected.
+    pub fn selected_entry<'h>(
+        &self,
+        topic: &str,
+        history: &HistoryView<'h>,
+        compare_brokers: bool,
+    ) -> Option<&'h HistoryEntry> {
+        let entries = table_entries(topic, history, compare_brokers);
+        let row = self.table_view.selected_row(topic, &row_keys(&entries));
+        entries.get(row).map(|(_, entry)| *entry)
+    }
+
+    pub fn change_selected_entry(
+        &mut self,
+        topic: &str,
+        history: &HistoryView,
+        compare_brokers: bool,
+        cursor_move: CursorMove,
+    ) {
+        let entries = table_entries(topic, history, compare_brokers);
+        self.table_view
+            .change_selected(topic, &row_keys(&entries), cursor_move);
+    }
+
+    pub fn toggle_diff(&mut self) {
+        self.diff = !self.diff;
+    }
+
+    /// Mark the selected entry to compare the other entries with it in the diff
+    pub fn toggle_mark(&mut self, topic: &str, history: &HistoryView, compare_brokers: bool) {
+        let entries = table_entries(topic, history, compare_brokers);
+        self.table_view.toggle_mark(topic, &row_keys(&entries));
+    }
+
+    /// Row the selected entry is compared with: the marked one or otherwise the previous one of the same broker
+    fn diff_base_row(&self, topic: &str, rows: &[RowKey]) -> Option<usize> {
+        if !self.diff {
+            return None;
+        }
+        let selected = self.table_view.selected_row(topic, rows);
+        let (broker, _) = rows.get(selected)?;
+        self.table_view
+            .marked_row(topic, rows)
+            .filter(|marked| *marked != selected)
+            .or_else(|| {
+                rows[..selected]
+                    .iter()
+                    .rposition(|(other, _)| other == broker)
+            })
+    }
+
+    /// Entry the one shown in the payload pane is compared with when in the diff mode
+    pub fn diff_base<'h>(
+        &self,
+        topic: &str,
+        history: &HistoryView<'h>,
+        compare_brokers: bool,
+    ) -> Option<&'h HistoryEntry> {
+        let entries = table_entries(topic, history, compare_brokers);
+        self.diff_base_row(topic, &row_keys(&entries))
+            .and_then(|row| entries.get(row))
+            .map(|(_, entry)| *entry)
+    }
+
+    /// `compare_brokers` shows the entries of the topic on all brokers in the history table
+    #[allow(clippy::too_many_arguments)]
     pub fn draw<B>(
         &mut self,
         f: &mut Frame<B>,
         area: Rect,
-        topic_history: &[HistoryEntry],
-        json_payload_has_focus: bool,
+        topic: &str,
+        history: &HistoryView,
+        compare_brokers: bool,
+        focus: &ElementInFocus,
+        now: DateTime<Local>,
     ) where
         B: Backend,
     {
         self.last_json_area = None;
 
-        let last = topic_history.last().unwrap();
-        let size = last.payload_size;
-        let history_area = match &last.payload {
+        let topic_history = history.get(topic).unwrap();
+        let dropped = history.get_dropped(topic);
+        let entries = table_entries(topic, history, compare_brokers);
+        let rows = row_keys(&entries);
+        let selected_row = self.table_view.prepare_draw(topic, &rows);
+        let selected = entries[selected_row].1;
+        let diff_base = self.diff_base_row(topic, &rows).map(|row| entries[row].1);
+        // Tell apart an older entry from the newest one which is updated live
+        let time = if self.table_view.follows_newest(topic) {
+            None
+        } else {
+            Some(selected.time.to_string())
+        };
+
+        let size = selected.payload_size;
+        let history_area = match &selected.payload {
             Payload::Json(json) => {
                 let (payload_area, remaining_area) = split_area_vertically(area, area.height / 4);
                 self.last_json_area = Some(payload_area);
+                let mut title = payload_title("JSON Payload", time.as_deref(), size);
+                let base =
+                    diff_base.and_then(|base| Some((base, base.payload.as_optional_json()?)));
+                let items = if let Some((base, base_json)) = base {
+                    let (items, changes) =
+                        json_view::root_tree_items_from_json_diff(json, base_json);
+                    title += &diff_summary(&base.time.to_string(), &changes);
+                    items
+                } else {
+                    root_tree_items_from_json(json)
+                };
+                write!(title, "  ({} to switch)", self.next_pane_key)
+                    .expect("write to string should never fail");
                 draw_payload_json(
                     f,
                     payload_area,
-                    size,
-                    json,
-                    json_payload_has_focus,
+                    title,
+                    items,
+                    matches!(focus, ElementInFocus::JsonPayload),
                     &mut self.json_view,
                 );
                 remaining_area
             }
-            Payload::NotUtf8(err) => draw_payload_string(f, area, size, &err.to_string()),
-            Payload::String(str) => draw_payload_string(f, area, size, str),
+            Payload::NotUtf8(err) => {
+                draw_payload_string(f, area, time.as_deref(), size, &err.to_string())
+            }
+            Payload::String(str) => draw_payload_string(f, area, time.as_deref(), size, str),
         };
 
-        history::draw(f, history_area, topic_history, &self.json_view.selected());
+        let json_selector = self.json_view.selected();
+        let mut graph_sources = Vec::with_capacity(self.pinned.len() + 1);
+        if self.pinned_index(topic).is_none() {
+            graph_sources.push(Source {
+                name: series_name(topic, selected.payload.as_optional_json(), &json_selector),
+                entries: topic_history,
+                json_selector: &json_selector,
+                y_axis: YAxis::Left,
+            });
+        }
+        graph_sources.extend(self.pinned.iter().filter_map(|pinned| {
+            history.get(&pinned.topic).map(|entries| Source {
+                name: pinned.name.clone(),
+                entries,
+                json_selector: &pinned.json_selector,
+                y_axis: pinned.y_axis,
+            })
+        }));
+
+        let table_area = history::draw_graph(
+            f,
+            history_area,
+            &graph_sources,
+            &mut self.graph_view,
+            matches!(focus, ElementInFocus::Graph),
+            now,
+        );
+        let across_brokers = entries
+            .iter()
+            .any(|(broker, _)| broker.is_some())
+            .then_some(entries.len());
+        let title = history::table_title(topic_history, dropped, across_brokers);
+        history::draw_table(
+            f,
+            table_area,
+            title,
+            &entries,
+            &json_selector,
+            &mut self.table_view,
+            matches!(focus, ElementInFocus::HistoryTable),
+        );
     }
 
     pub fn json_index_of_click(&mut self, column: u16, row: u16) -> Option<usize> {
@@ -69,12 +275,81 @@ impl Details {
     }
 }
 
+/// The entries of the topic or, when comparing brokers, of the topic on all brokers
+fn table_entries<'h>(
+    topic: &str,
+    history: &HistoryView<'h>,
+    compare_brokers: bool,
+) -> Vec<(Option<&'h str>, &'h HistoryEntry)> {
+    let across_brokers = if compare_brokers {
+        history.get_across_brokers(topic)
+    } else {
+        Vec::new()
+    };
+    if across_brokers.is_empty() {
+        history
+            .get(topic)
+            .into_iter()
+            .flatten()
+            .map(|entry| (None, entry))
+            .collect()
+    } else {
+        across_brokers
+            .into_iter()
+            .map(|(broker, entry)| (Some(broker), entry))
+            .collect()
+    }
+}
+
+fn row_keys<'h>(entries: &[(Option<&'h str>, &HistoryEntry)]) -> Vec<RowKey<'h>> {
+    entries
+        .iter()
+        .map(|(broker, entry)| (*broker, entry.received))
+        .collect()
+}
+
+fn series_name(topic: &str, json: Option<&JsonValue>, json_selector: &[usize]) -> String {
+    let path = json
+        .map(|json| json_view::get_selected_path(json, json_selector))
+        .unwrap_or_default();
+    if path.is_empty() {
+        topic.to_string()
+    } else {
+        format!("{topic} {path}")
+    }
+}
+
+fn diff_summary(base_time: &str, changes: &[(String, Change)]) -> String {
+    let amount_of = |change| changes.iter().filter(|(_, c)| *c == change).count();
+    format!(
+        " diff to {}: {} changed, {} added, {} removed",
+        base_time.trim(),
+        amount_of(Change::Changed),
+        amount_of(Change::Added),
+        amount_of(Change::Removed)
+    )
+}
+
+/// Title of the payload including the time when it is an older entry
+fn payload_title(name: &str, time: Option<&str>, bytes: usize) -> String {
+    time.map_or_else(
+        || format!("{name} (Bytes: {bytes})"),
+        |time| format!("{name} of {} (Bytes: {bytes})", time.trim()),
+    )
+}
+
 /// Returns remaining rect to be used for history
-fn draw_payload_string<B>(f: &mut Frame<B>, area: Rect, payload_bytes: usize, payload: &str) -> Rect
+fn draw_payload_string<B>(
+    f: &mut Frame<B>,
+    area: Rect,
+    time: Option<&str>,
+    payload_bytes: usize,
+    payload: &str,
+) -> Rect
 where
     B: Backend,
 {
-    let title = format!("Payload (Bytes: {payload_bytes})");
+    let title = payload_title("Payload", time, payload_bytes);
     let items = payload.lines().map(ListItem::new).collect::<Vec<_>>();
 
     let max_payload_height = area.height / 3;
@@ -90,15 +365,13 @@ where
 fn draw_payload_json<B>(
     f: &mut Frame<B>,
     area: Rect,
-    bytes: usize,
-    json: &JsonValue,
+    title: String,
+    items: Vec<TreeItem>,
     has_focus: bool,
     view_state: &mut TreeState,
 ) where
     B: Backend,
 {
-    let title = format!("JSON Payload (Bytes: {bytes})  (TAB to switch)");
-    let items = root_tree_items_from_json(json);
     let focus_color = focus_color(has_focus);
     let widget = Tree::new(items)
         .block(
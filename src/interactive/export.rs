@@ -0,0 +1,384 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crossterm::event::KeyEvent;
+use json::JsonValue;
+use tui::backend::Backend;
+use tui::layout::Rect;
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, Paragraph};
+use tui::Frame;
+use tui_textarea::TextArea;
+
+use crate::interactive::ui::{focus_color, split_area_vertically};
+use crate::json_view;
+use crate::mqtt::{capture, HistoryEntry, Payload, Time};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    JsonLines,
+    /// The format used to persist the history, see [`capture`]
+    Raw,
+}
+
+impl Format {
+    const fn next(self) -> Self {
+        match self {
+            Self::Csv => Self::JsonLines,
+            Self::JsonLines => Self::Raw,
+            Self::Raw => Self::Csv,
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Csv => "CSV",
+            Self::JsonLines => "JSON Lines",
+            Self::Raw => "raw capture",
+        }
+    }
+
+    const fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::JsonLines => "jsonl",
+            Self::Raw => "mqttui",
+        }
+    }
+}
+
+/// Popup to export the history of the selected topic into a file
+pub struct Export<'a> {
+    topic: String,
+    format: Format,
+    subtree: bool,
+    path: TextArea<'a>,
+    result: Option<Result<String, String>>,
+}
+
+impl Export<'_> {
+    /// Topics without own history can only be exported including the topics below
+    pub fn new(topic: String, has_history: bool) -> Self {
+        let format = Format::Csv;
+        let file = format!(
+            "{}-{}.{}",
+            topic.replace('/', "_"),
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            format.extension()
+        );
+        Self {
+            topic,
+            format,
+            subtree: !has_history,
+            path: TextArea::new(vec![file]),
+            result: None,
+        }
+    }
+
+    pub fn get_topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub const fn get_format(&self) -> Format {
+        self.format
+    }
+
+    pub const fn is_subtree(&self) -> bool {
+        self.subtree
+    }
+
+    /// The result is shown until the popup is closed
+    pub const fn is_done(&self) -> bool {
+        self.result.is_some()
+    }
+
+    pub fn get_path(&self) -> &str {
+        self.path.lines()[0].trim()
+    }
+
+    /// Switch to the next format and adapt the file extension when it is still the one of the previous format
+    pub fn next_format(&mut self) {
+        let previous = self.format;
+        self.format = previous.next();
+        if let Some(stem) = self
+            .get_path()
+            .strip_suffix(previous.extension())
+            .map(ToString::to_string)
+        {
+            self.path = TextArea::new(vec![format!("{stem}{}", self.format.extension())]);
+            self.path.move_cursor(tui_textarea::CursorMove::End);
+        }
+    }
+
+    pub fn toggle_subtree(&mut self) {
+        self.subtree = !self.subtree;
+    }
+
+    pub fn input(&mut self, key: KeyEvent) {
+        self.path.input(key);
+    }
+
+    pub fn set_result(&mut self, result: anyhow::Result<usize>) {
+        self.result = Some(
+            result
+                .map(|amount| format!("Exported {amount} messages to {}", self.get_path()))
+                .map_err(|err| format!("Export failed: {err:#}")),
+        );
+    }
+
+    pub fn draw<B>(&mut self, f: &mut Frame<B>)
+    where
+        B: Backend,
+    {
+        let area = popup_area(f.size());
+        f.render_widget(Clear, area); // clear the background of the popup
+        let (info_area, path_area) = split_area_vertically(area, area.height.saturating_sub(3));
+
+        let scope = if self.subtree {
+            "with all topics below"
+        } else {
+            "only this topic"
+        };
+        let mut text = vec![
+            Spans::from(Span::styled(
+                self.topic.as_str(),
+                Style::default().add_modifier(Modifier::BOLD | Modifier::ITALIC),
+            )),
+            Spans::from(format!("Scope: {scope}")),
+            Spans::from(format!("Format: {}", self.format.name())),
+        ];
+        match &self.result {
+            Some(Ok(message)) => text.push(Spans::from(Span::styled(
+                message.as_str(),
+                Style::default().fg(Color::Green),
+            ))),
+            Some(Err(message)) => text.push(Spans::from(Span::styled(
+                message.as_str(),
+                Style::default().fg(Color::Red),
+            ))),
+            None => {}
+        }
+
+        let focus_color = focus_color(true);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(focus_color))
+            .title("Export history");
+        f.render_widget(Paragraph::new(text).block(block), info_area);
+
+        self.path.set_block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(focus_color))
+                .title("File"),
+        );
+        f.render_widget(self.path.widget(), path_area);
+    }
+}
+
+/// Export the entries into the file. Returns the amount of exported entries.
+pub fn to_file(
+    path: &Path,
+    format: Format,
+    entries: &[(&str, &HistoryEntry)],
+    json_selector: &[usize],
+) -> anyhow::Result<usize> {
+    anyhow::ensure!(!path.as_os_str().is_empty(), "no file given");
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer, format, entries, json_selector)?;
+    writer.flush()?;
+    Ok(entries.len())
+}
+
+/// Write the entries in the given format.
+/// The value column contains the JSON sub-value selected by `json_selector` like the history table does.
+pub fn write(
+    writer: &mut impl Write,
+    format: Format,
+    entries: &[(&str, &HistoryEntry)],
+    json_selector: &[usize],
+) -> io::Result<()> {
+    match format {
+        Format::Csv => {
+            writeln!(writer, "time,retained,topic,qos,payload,value")?;
+            for (topic, entry) in entries {
+                let payload = String::from_utf8_lossy(&entry.raw);
+                let value = match selected_value(entry, json_selector) {
+                    JsonValue::String(str) => str,
+                    JsonValue::Short(str) => str.to_string(),
+                    JsonValue::Null => String::new(),
+                    json => json.dump(),
+                };
+                writeln!(
+                    writer,
+                    "{},{},{},{},{},{}",
+                    entry.received.to_rfc3339(),
+                    is_retained(entry),
+                    csv_field(topic),
+                    entry.qos as u8,
+                    csv_field(&payload),
+                    csv_field(&value),
+                )?;
+            }
+        }
+        Format::JsonLines => {
+            for (topic, entry) in entries {
+                let payload = match &entry.payload {
+                    Payload::Json(json) => json.clone(),
+                    Payload::String(str) => JsonValue::from(str.as_ref()),
+                    // Keep the bytes as they are not representable as a JSON string
+                    Payload::NotUtf8(_) => JsonValue::from(entry.raw.to_vec()),
+                };
+                let line = json::object! {
+                    time: entry.received.to_rfc3339(),
+                    retained: is_retained(entry),
+                    topic: *topic,
+                    qos: entry.qos as u8,
+                    payload: payload,
+                    value: selected_value(entry, json_selector),
+                };
+                writeln!(writer, "{}", line.dump())?;
+            }
+        }
+        Format::Raw => {
+            writer.write_all(capture::MAGIC)?;
+            for (topic, entry) in entries {
+                capture::write_record(writer, &entry.to_packet(topic), entry.received)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+const fn is_retained(entry: &HistoryEntry) -> bool {
+    matches!(entry.time, Time::Retained)
+}
+
+/// Value shown in the history table
+fn selected_value(entry: &HistoryEntry, json_selector: &[usize]) -> JsonValue {
+    match &entry.payload {
+        Payload::Json(json) => json_view::get_selected_subvalue(json, json_selector)
+            .unwrap_or(json)
+            .clone(),
+        Payload::String(str) => JsonValue::from(str.as_ref()),
+        Payload::NotUtf8(_) => JsonValue::Null,
+    }
+}
+
+/// Quote the field when necessary according to RFC 4180
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn popup_area(r: Rect) -> Rect {
+    let height = 9;
+    // The order is important here. Clamp just panics on min > max which is not what is wanted.
+    #[allow(clippy::manual_clamp)]
+    let width = (r.width.saturating_mul(4) / 5)
+        .max(60)
+        .min(r.width.saturating_sub(4));
+    let x = (r.width - width) / 2;
+    let y = r.height.saturating_sub(height) / 2;
+    Rect::new(x, y, width, height.min(r.height))
+}
+
+#[cfg(test)]
+fn example_entries() -> Vec<HistoryEntry> {
+    let time = chrono::DateTime::parse_from_rfc3339("2023-05-17T12:00:00+02:00")
+        .unwrap()
+        .into();
+    let mut retained = rumqttc::Publish::new(
+        "",
+        rumqttc::QoS::AtLeastOnce,
+        r#"{"a": [1, 2], "b": "x,y"}"#,
+    );
+    retained.retain = true;
+    vec![
+        HistoryEntry::new(&retained, time),
+        HistoryEntry::new(
+            &rumqttc::Publish::new("", rumqttc::QoS::AtMostOnce, "say \"hi\""),
+            time,
+        ),
+    ]
+}
+
+#[cfg(test)]
+fn export_example(format: Format, json_selector: &[usize]) -> String {
+    let entries = example_entries();
+    let entries = entries
+        .iter()
+        .map(|entry| ("foo/bar", entry))
+        .collect::<Vec<_>>();
+    let mut output = Vec::new();
+    write(&mut output, format, &entries, json_selector).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn csv_works() {
+    let time = chrono::DateTime::<chrono::Local>::from(
+        chrono::DateTime::parse_from_rfc3339("2023-05-17T12:00:00+02:00").unwrap(),
+    )
+    .to_rfc3339();
+    assert_eq!(
+        export_example(Format::Csv, &[0]),
+        format!(
+            "time,retained,topic,qos,payload,value\n\
+            {time},true,foo/bar,1,\"{{\"\"a\"\": [1, 2], \"\"b\"\": \"\"x,y\"\"}}\",\"[1,2]\"\n\
+            {time},false,foo/bar,0,\"say \"\"hi\"\"\",\"say \"\"hi\"\"\"\n"
+        )
+    );
+}
+
+#[test]
+fn json_lines_works() {
+    let lines = export_example(Format::JsonLines, &[1]);
+    let lines = lines
+        .lines()
+        .map(|line| json::parse(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["retained"], true);
+    assert_eq!(lines[0]["topic"], "foo/bar");
+    assert_eq!(lines[0]["payload"]["a"][1], 2);
+    assert_eq!(lines[0]["value"], "x,y");
+    assert_eq!(lines[1]["payload"], "say \"hi\"");
+    assert_eq!(lines[1]["value"], "say \"hi\"");
+}
+
+#[test]
+fn raw_is_readable_capture() {
+    let entries = example_entries();
+    let entries = entries
+        .iter()
+        .map(|entry| ("foo/bar", entry))
+        .collect::<Vec<_>>();
+    let mut output = Vec::new();
+    write(&mut output, Format::Raw, &entries, &[]).unwrap();
+    let packets = capture::Reader::new(output.as_slice())
+        .unwrap()
+        .map(|record| record.unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(packets.len(), 2);
+    assert!(packets[0].retain);
+    assert_eq!(packets[1].payload, "say \"hi\"");
+}
+
+#[test]
+fn next_format_changes_extension() {
+    let mut export = Export::new("foo".to_string(), true);
+    assert_eq!(Path::new(export.get_path()).extension().unwrap(), "csv");
+    export.next_format();
+    assert_eq!(export.get_format(), Format::JsonLines);
+    assert_eq!(Path::new(export.get_path()).extension().unwrap(), "jsonl");
+    export.path = TextArea::new(vec!["data.txt".to_string()]);
+    export.next_format();
+    assert_eq!(export.get_path(), "data.txt");
+}
@@ -5,41 +5,83 @@ use tui::text::{Span, Spans};
 use tui::widgets::{Paragraph, Wrap};
 use tui::Frame;
 
-use crate::cli::Broker;
+use crate::interactive::mqtt_history::STYLE_DARKGRAY;
 use crate::interactive::ui::STYLE_BOLD;
+use crate::mqtt::ServerCertificateArc;
+
+const STYLE_ERROR: Style = Style {
+    fg: Some(Color::Red),
+    bg: None,
+    add_modifier: Modifier::BOLD,
+    sub_modifier: Modifier::empty(),
+};
 
 pub struct InfoHeader {
     title: String,
+    server_certificate: Option<ServerCertificateArc>,
 }
 
 impl InfoHeader {
-    pub fn new(broker: &Broker) -> Self {
+    pub fn new(broker: &str, server_certificate: Option<ServerCertificateArc>) -> Self {
         Self {
-            title: format!("MQTT TUI {} @ {broker:?}", env!("CARGO_PKG_VERSION")),
+            title: format!("MQTT TUI {} @ {broker}", env!("CARGO_PKG_VERSION")),
+            server_certificate,
         }
     }
 
+    fn server_certificate_text(&self) -> Option<String> {
+        let server_certificate = self.server_certificate.as_ref()?.read().ok()?;
+        server_certificate.as_ref().map(|cert| {
+            format!(
+                "TLS: {} issued by {} valid until {}",
+                cert.subject,
+                cert.issuer,
+                cert.not_after.format("%Y-%m-%d")
+            )
+        })
+    }
+
     pub fn draw<B>(
         &self,
         f: &mut Frame<B>,
         area: Rect,
         connection_error: Option<String>,
+        rejected_subscriptions: &[&str],
+        history_error: Option<&str>,
         selected_topic: &Option<String>,
     ) where
         B: Backend,
     {
         let mut text = vec![Spans::from(self.title.as_str())];
 
+        if let Some(server_certificate) = self.server_certificate_text() {
+            text.push(Spans::from(Span::styled(
+                server_certificate,
+                STYLE_DARKGRAY,
+            )));
+        }
+
         if let Some(err) = connection_error {
-            const STYLE: Style = Style {
-                fg: Some(Color::Red),
-                bg: None,
-                add_modifier: Modifier::BOLD,
-                sub_modifier: Modifier::empty(),
-            };
             text.push(Spans::from(Span::styled(
                 format!("MQTT Connection Error: {err}"),
-                STYLE,
+                STYLE_ERROR,
+            )));
+        }
+
+        if !rejected_subscriptions.is_empty() {
+            text.push(Spans::from(Span::styled(
+                format!(
+                    "Subscription rejected by the broker: {}",
+                    rejected_subscriptions.join(", ")
+                ),
+                STYLE_ERROR,
+            )));
+        }
+
+        if let Some(err) = history_error {
+            text.push(Spans::from(Span::styled(
+                format!("Persisting the history failed: {err}"),
+                STYLE_ERROR,
             )));
         }
 
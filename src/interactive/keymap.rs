@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Panes which are navigated with the [`KeyMap`].
+/// Text inputs and popups have fixed keys as they need most keys for the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Topics,
+    Payload,
+    History,
+    Watchlist,
+    Graph,
+}
+
+const ALL: &[Pane] = &[
+    Pane::Topics,
+    Pane::Payload,
+    Pane::History,
+    Pane::Watchlist,
+    Pane::Graph,
+];
+
+/// Everything the keys can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Pause,
+    PreviousBroker,
+    NextBroker,
+    NextPane,
+    Back,
+    Toggle,
+    Up,
+    Down,
+    Left,
+    Right,
+    First,
+    Last,
+    PageUp,
+    PageDown,
+    CollapseAll,
+    ExpandAll,
+    Delete,
+    Search,
+    Watch,
+    Watchlist,
+    Subscriptions,
+    Sort,
+    Export,
+    Pin,
+    PinSecondAxis,
+    Graph,
+    Diff,
+    Mark,
+    TimeWindow,
+    ZoomIn,
+    ZoomOut,
+    PanBack,
+    PanForward,
+}
+
+type ActionInfo = (
+    Action,
+    &'static str,
+    &'static [&'static str],
+    &'static [Pane],
+);
+
+/// Every [`Action`] with its name in the key bindings file, its default keys and the panes it is available in
+const ACTIONS: &[ActionInfo] = &[
+    (Action::Quit, "quit", &["q", "ctrl+c"], ALL),
+    (Action::Pause, "pause", &["P"], ALL),
+    (
+        Action::PreviousBroker,
+        "previous-broker",
+        &["["],
+        &[Pane::Topics, Pane::Payload],
+    ),
+    (
+        Action::NextBroker,
+        "next-broker",
+        &["]"],
+        &[Pane::Topics, Pane::Payload],
+    ),
+    (Action::NextPane, "next-pane", &["tab", "backtab"], ALL),
+    (
+        Action::Back,
+        "back",
+        &["esc"],
+        &[Pane::Topics, Pane::History, Pane::Watchlist, Pane::Graph],
+    ),
+    (
+        Action::Toggle,
+        "toggle",
+        &["enter", "space"],
+        &[Pane::Topics, Pane::Payload, Pane::Watchlist],
+    ),
+    (
+        Action::Up,
+        "up",
+        &["up", "k"],
+        &[Pane::Topics, Pane::Payload, Pane::History, Pane::Watchlist],
+    ),
+    (
+        Action::Down,
+        "down",
+        &["down", "j"],
+        &[Pane::Topics, Pane::Payload, Pane::History, Pane::Watchlist],
+    ),
+    (
+        Action::Left,
+        "left",
+        &["left", "h"],
+        &[Pane::Topics, Pane::Payload, Pane::Graph],
+    ),
+    (
+        Action::Right,
+        "right",
+        &["right", "l"],
+        &[Pane::Topics, Pane::Payload, Pane::Graph],
+    ),
+    (
+        Action::First,
+        "first",
+        &["home"],
+        &[Pane::Topics, Pane::Payload, Pane::History],
+    ),
+    (
+        Action::Last,
+        "last",
+        &["end"],
+        &[Pane::Topics, Pane::Payload, Pane::History, Pane::Graph],
+    ),
+    (
+        Action::PageUp,
+        "page-up",
+        &["pageup", "ctrl+u"],
+        &[Pane::Topics, Pane::History],
+    ),
+    (
+        Action::PageDown,
+        "page-down",
+        &["pagedown", "ctrl+d"],
+        &[Pane::Topics, Pane::History],
+    ),
+    (Action::CollapseAll, "collapse-all", &["H"], &[Pane::Topics]),
+    (Action::ExpandAll, "expand-all", &["L"], &[Pane::Topics]),
+    (
+        Action::Delete,
+        "delete",
+        &["delete", "backspace"],
+        &[Pane::Topics, Pane::Watchlist],
+    ),
+    (Action::Search, "search", &["/"], &[Pane::Topics]),
+    (
+        Action::Watch,
+        "watch",
+        &["w"],
+        &[Pane::Topics, Pane::Watchlist],
+    ),
+    (
+        Action::Watchlist,
+        "watchlist",
+        &["W"],
+        &[Pane::Topics, Pane::Watchlist],
+    ),
+    (
+        Action::Subscriptions,
+        "subscriptions",
+        &["s"],
+        &[Pane::Topics],
+    ),
+    (Action::Sort, "sort", &["o"], &[Pane::Topics]),
+    (
+        Action::Export,
+        "export",
+        &["e"],
+        &[Pane::Topics, Pane::Payload],
+    ),
+    (Action::Pin, "pin", &["p"], &[Pane::Topics, Pane::Payload]),
+    (
+        Action::PinSecondAxis,
+        "pin-second-axis",
+        &["a"],
+        &[Pane::Topics, Pane::Payload],
+    ),
+    (
+        Action::Graph,
+        "graph",
+        &["g"],
+        &[Pane::Topics, Pane::Payload, Pane::Graph],
+    ),
+    (
+        Action::Diff,
+        "diff",
+        &["d"],
+        &[Pane::Payload, Pane::History],
+    ),
+    (Action::Mark, "mark", &["m"], &[Pane::History]),
+    (Action::TimeWindow, "time-window", &["w"], &[Pane::Graph]),
+    (Action::ZoomIn, "zoom-in", &["+", "="], &[Pane::Graph]),
+    (Action::ZoomOut, "zoom-out", &["-"], &[Pane::Graph]),
+    (Action::PanBack, "pan-back", &["<", ","], &[Pane::Graph]),
+    (
+        Action::PanForward,
+        "pan-forward",
+        &[">", "."],
+        &[Pane::Graph],
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    code: KeyCode,
+    /// Without shift as it is already part of the code like `H` or `BackTab`
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn matches(self, event: KeyEvent) -> bool {
+        self.code == event.code && self.modifiers == event.modifiers - KeyModifiers::SHIFT
+    }
+}
+
+impl core::str::FromStr for Key {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn strip_modifier<'s>(s: &'s str, modifier: &str) -> Option<&'s str> {
+            let prefix = s.get(..modifier.len())?;
+            let rest = &s[modifier.len()..];
+            (prefix.eq_ignore_ascii_case(modifier) && !rest.is_empty()).then_some(rest)
+        }
+
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            if let Some(after) = strip_modifier(rest, "ctrl+") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = after;
+            } else if let Some(after) = strip_modifier(rest, "alt+") {
+                modifiers |= KeyModifiers::ALT;
+                rest = after;
+            } else {
+                break;
+            }
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(char), None) => KeyCode::Char(char),
+            _ => match rest.to_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "delete" => KeyCode::Delete,
+                "backspace" => KeyCode::Backspace,
+                _ => anyhow::bail!("unknown key {s:?}"),
+            },
+        };
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            f.write_str("Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            f.write_str("Alt+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(char) => write!(f, "{char}"),
+            KeyCode::Tab => f.write_str("Tab"),
+            KeyCode::BackTab => f.write_str("Shift+Tab"),
+            KeyCode::Enter => f.write_str("Enter"),
+            KeyCode::Esc => f.write_str("Esc"),
+            KeyCode::Up => f.write_str("↑"),
+            KeyCode::Down => f.write_str("↓"),
+            KeyCode::Left => f.write_str("←"),
+            KeyCode::Right => f.write_str("→"),
+            KeyCode::Home => f.write_str("Home"),
+            KeyCode::End => f.write_str("End"),
+            KeyCode::PageUp => f.write_str("PgUp"),
+            KeyCode::PageDown => f.write_str("PgDn"),
+            KeyCode::Delete => f.write_str("Del"),
+            KeyCode::Backspace => f.write_str("Backspace"),
+            other => write!(f, "{other:?}"),
+        }
+    }
+}
+
+/// Keys of the [`Action`]s, the defaults can be changed with a key bindings file
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    keys: HashMap<Action, Vec<Key>>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let keys = ACTIONS
+            .iter()
+            .map(|(action, _name, keys, _panes)| {
+                let keys = keys
+                    .iter()
+                    .map(|key| key.parse().expect("default keys are valid"))
+                    .collect();
+                (*action, keys)
+            })
+            .collect();
+        Self { keys }
+    }
+}
+
+impl KeyMap {
+    /// The default keys with the ones of the file when given
+    pub fn load(file: Option<&Path>) -> anyhow::Result<Self> {
+        let mut keymap = Self::default();
+        if let Some(file) = file {
+            let content = fs::read_to_string(file).map_err(|err| {
+                anyhow::anyhow!("failed to read key bindings {}: {err}", file.display())
+            })?;
+            keymap.apply(&content).map_err(|err| {
+                anyhow::anyhow!("invalid key bindings in {}: {err}", file.display())
+            })?;
+        }
+        Ok(keymap)
+    }
+
+    /// Lines like `quit = q ctrl+c` replace the keys of the action. Lines starting with `#` are ignored.
+    fn apply(&mut self, content: &str) -> anyhow::Result<()> {
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_number = index + 1;
+            let (name, keys) = line.split_once('=').ok_or_else(|| {
+                anyhow::anyhow!("line {line_number} has to be '<action> = <keys>'")
+            })?;
+            let name = name.trim();
+            let (action, ..) = ACTIONS
+                .iter()
+                .find(|info| info.1 == name)
+                .ok_or_else(|| anyhow::anyhow!("line {line_number}: unknown action {name:?}"))?;
+            let keys = keys
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<anyhow::Result<Vec<Key>>>()
+                .map_err(|err| anyhow::anyhow!("line {line_number}: {err}"))?;
+            self.keys.insert(*action, keys);
+        }
+        self.validate()
+    }
+
+    /// Actions available in the same pane must not share a key
+    fn validate(&self) -> anyhow::Result<()> {
+        for pane in ALL {
+            let mut seen = HashMap::<Key, &str>::new();
+            for (action, name, _keys, panes) in ACTIONS {
+                if !panes.contains(pane) {
+                    continue;
+                }
+                for key in self.keys.get(action).into_iter().flatten() {
+                    if let Some(other) = seen.insert(*key, name) {
+                        anyhow::bail!(
+                            "{key} is bound to {other} and {name} which are both available in the {pane:?} pane"
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Action of the key available in the pane
+    pub fn action(&self, pane: Pane, event: KeyEvent) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .filter(|(_action, _name, _keys, panes)| panes.contains(&pane))
+            .map(|(action, ..)| *action)
+            .find(|action| {
+                self.keys
+                    .get(action)
+                    .map_or(false, |keys| keys.iter().any(|key| key.matches(event)))
+            })
+    }
+
+    /// The first key of the action for the key hints. Empty when the action is not bound.
+    pub fn hint(&self, action: Action) -> String {
+        self.keys
+            .get(&action)
+            .and_then(|keys| keys.first())
+            .map_or_else(String::new, ToString::to_string)
+    }
+}
+
+#[cfg(test)]
+fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+    KeyEvent::new(code, modifiers)
+}
+
+#[test]
+fn defaults_have_no_conflicts() {
+    let keymap = KeyMap::default();
+    keymap.validate().unwrap();
+    let action = |pane, code, modifiers| keymap.action(pane, event(code, modifiers));
+    assert_eq!(
+        action(Pane::Topics, KeyCode::Char('w'), KeyModifiers::NONE),
+        Some(Action::Watch)
+    );
+    assert_eq!(
+        action(Pane::Graph, KeyCode::Char('w'), KeyModifiers::NONE),
+        Some(Action::TimeWindow)
+    );
+    assert_eq!(
+        action(Pane::Topics, KeyCode::Char('H'), KeyModifiers::SHIFT),
+        Some(Action::CollapseAll)
+    );
+    assert_eq!(
+        action(Pane::Topics, KeyCode::Char('c'), KeyModifiers::CONTROL),
+        Some(Action::Quit)
+    );
+    assert_eq!(
+        action(Pane::Topics, KeyCode::Char('c'), KeyModifiers::NONE),
+        None
+    );
+    assert_eq!(
+        action(Pane::History, KeyCode::BackTab, KeyModifiers::SHIFT),
+        Some(Action::NextPane)
+    );
+}
+
+#[test]
+fn file_replaces_keys() {
+    let mut keymap = KeyMap::default();
+    keymap
+        .apply("# vim keys are not for everyone\nup = up i\ndown = down\nquit = ctrl+q\n")
+        .unwrap();
+    assert_eq!(
+        keymap.action(Pane::Topics, event(KeyCode::Char('i'), KeyModifiers::NONE)),
+        Some(Action::Up)
+    );
+    assert_eq!(
+        keymap.action(Pane::Topics, event(KeyCode::Char('j'), KeyModifiers::NONE)),
+        None
+    );
+    assert_eq!(keymap.hint(Action::Quit), "Ctrl+q");
+    assert_eq!(keymap.hint(Action::Toggle), "Enter");
+}
+
+#[test]
+fn invalid_files_are_rejected() {
+    let err = KeyMap::default().apply("up = w").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "w is bound to up and watch which are both available in the Topics pane"
+    );
+    assert!(KeyMap::default().apply("fly = f").is_err());
+    assert!(KeyMap::default().apply("up = hyper+k").is_err());
+    assert!(KeyMap::default().apply("up").is_err());
+    // Time window and watch are never available in the same pane
+    KeyMap::default()
+        .apply("time-window = x\nwatch = x")
+        .unwrap();
+}
@@ -1,9 +1,13 @@
-use std::collections::HashSet;
+use std::fs;
 use std::io::stdout;
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Local};
 use crossterm::event::{
     DisableMouseCapture, EnableMouseCapture, Event as CEvent, KeyCode, KeyEvent, KeyModifiers,
     MouseButton, MouseEventKind,
@@ -15,32 +19,51 @@ use crossterm::terminal::{
 use json::JsonValue;
 use rumqttc::{Client, Connection};
 use tui::backend::Backend;
-use tui::layout::Rect;
+use tui::layout::{Alignment, Rect};
 use tui::style::{Color, Modifier, Style};
 use tui::text::{Span, Spans};
-use tui::widgets::Paragraph;
+use tui::widgets::{Paragraph, Tabs};
 use tui::Frame;
 use tui::{backend::CrosstermBackend, Terminal};
-use tui_textarea::TextArea;
 use tui_tree_widget::flatten;
 
 use crate::cli::Broker;
-use crate::interactive::ui::CursorMove;
-use crate::json_view::root_tree_items_from_json;
+use crate::interactive::keymap::{Action, Pane};
+use crate::interactive::mqtt_history::{HistoryView, STYLE_DARKGRAY};
+use crate::interactive::ui::{focus_color, split_area_vertically, CursorMove};
+use crate::json_view;
+use crate::mqtt::topic::TopicSet;
+use crate::mqtt::ServerCertificateArc;
 
+mod alerts;
 mod clean_retained;
 mod details;
+mod export;
 mod info_header;
+mod keymap;
 mod mqtt_history;
 mod mqtt_thread;
+mod search;
+mod subscriptions;
 mod topic_overview;
 mod ui;
+mod watchlist;
+
+pub use alerts::Alerting;
+pub use keymap::KeyMap;
+pub use mqtt_history::HistoryLimits;
+pub use topic_overview::AutoExpand;
 
 enum ElementInFocus {
     TopicOverview,
     JsonPayload,
     CleanRetainedPopup(String),
     SearchMode,
+    Subscriptions,
+    ExportPopup,
+    Graph,
+    HistoryTable,
+    Watchlist,
 }
 
 enum Event {
@@ -60,14 +83,94 @@ enum Refresh {
     Quit,
 }
 
+/// Everything needed to show one broker
+pub struct BrokerConnection {
+    pub broker: Broker,
+    pub client: Client,
+    pub connection: Connection,
+    pub server_certificate: Option<ServerCertificateArc>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn show(
-    client: Client,
-    connection: Connection,
-    broker: &Broker,
-    subscribe_topic: Vec<String>,
+    connections: Vec<BrokerConnection>,
+    subscribe_topic: &[String],
+    history_limits: HistoryLimits,
+    history_dir: Option<&Path>,
+    auto_expand: &AutoExpand,
+    watchlist_file: Option<&Path>,
+    alerting: &Alerting,
+    keys: &KeyMap,
 ) -> anyhow::Result<()> {
-    let mqtt_thread = mqtt_thread::MqttThread::new(client, connection, subscribe_topic)?;
-    let mut app = App::new(broker, mqtt_thread);
+    if let Some(dir) = history_dir {
+        fs::create_dir_all(dir)?;
+    }
+
+    // With multiple brokers there is an additional tab showing all of them in one tree
+    let mut merged_histories = Vec::new();
+    let mut tabs = BrokerTabs::default();
+    let mut tree_labels = Vec::<String>::new();
+    let watchlist_file = watchlist_file
+        .map(Path::to_path_buf)
+        .or_else(watchlist::default_file);
+    let pins = watchlist::Pins::load(watchlist_file.as_deref())?;
+    for BrokerConnection {
+        broker,
+        client,
+        connection,
+        server_certificate,
+    } in connections
+    {
+        let mut label = tree_label(&broker);
+        if tree_labels.contains(&label) {
+            label = format!("{label} ({})", tree_labels.len() + 1);
+        }
+        tree_labels.push(label.clone());
+
+        let history_file = history_dir.map(|dir| dir.join(history_file_name(&label, &broker)));
+        let mqtt_thread = mqtt_thread::MqttThread::new(
+            client,
+            connection,
+            subscribe_topic.to_vec(),
+            history_limits,
+            history_file.as_deref(),
+            &alerting.rules,
+        )?;
+        let title = broker.to_string();
+        let history = mqtt_thread.get_history_arc();
+        merged_histories.push((Some(label.clone()), Arc::clone(&history)));
+        let info_header = info_header::InfoHeader::new(&title, server_certificate);
+        tabs.titles.push(title);
+        let watchlist = watchlist::Watchlist::new(Rc::clone(&pins), Some(label.clone()));
+        let app = App::new(
+            info_header,
+            Some(mqtt_thread),
+            vec![(None, history)],
+            auto_expand,
+            watchlist,
+            alerting,
+            keys,
+        );
+        tabs.apps.push(app);
+    }
+    for mqtt_thread in tabs.apps.iter().filter_map(|app| app.mqtt_thread.as_ref()) {
+        mqtt_thread.wait_connected()?;
+    }
+    if merged_histories.len() > 1 {
+        let info_header = info_header::InfoHeader::new("all brokers", None);
+        tabs.titles.push("All brokers".to_string());
+        let watchlist = watchlist::Watchlist::new(pins, None);
+        let app = App::new(
+            info_header,
+            None,
+            merged_histories,
+            auto_expand,
+            watchlist,
+            alerting,
+            keys,
+        );
+        tabs.apps.push(app);
+    }
 
     enable_raw_mode()?;
 
@@ -78,7 +181,26 @@ pub fn show(
 
     let mut terminal = Terminal::new(backend)?;
 
-    // Setup input handling
+    let rx = spawn_input_thread();
+
+    terminal.clear()?;
+
+    let main_loop_result = main_loop(&mut tabs, &rx, &mut terminal);
+
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    main_loop_result?;
+    tabs.flush_histories()
+}
+
+/// Events of the terminal and ticks to redraw regularly
+fn spawn_input_thread() -> Receiver<Event> {
     let (tx, rx) = mpsc::channel();
 
     thread::spawn(move || {
@@ -121,29 +243,52 @@ pub fn show(
             }
         }
     });
+    rx
+}
 
-    terminal.clear()?;
-
-    let main_loop_result = main_loop(&mut app, &rx, &mut terminal);
-
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+/// Name of the top-level node of the broker in the merged tree like `mqtts:localhost:8883`.
+/// The scheme and path tell brokers on the same port apart, the `/` of paths are percent-encoded to stay a single level.
+fn tree_label(broker: &Broker) -> String {
+    broker
+        .to_string()
+        .replacen("://", ":", 1)
+        .trim_end_matches('/')
+        .replace('%', "%25")
+        .replace('/', "%2F")
+}
 
-    main_loop_result
+/// File name of the persisted history. Characters which might not be allowed in file names are replaced.
+/// A hash of the URL keeps the files of brokers apart which only differ in these characters.
+fn history_file_name(label: &str, broker: &Broker) -> String {
+    // Replace the slashes of the path like the other characters instead of their encoding
+    let name = label
+        .replace("%2F", "/")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '(' | ')') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    // FNV-1a as the hashers of the standard library may change between releases
+    let hash = broker
+        .to_string()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{name}-{:08x}.mqttui", hash >> 32)
 }
 
-fn terminal_draw<B>(app: &mut App, terminal: &mut Terminal<B>) -> anyhow::Result<()>
+fn terminal_draw<B>(tabs: &mut BrokerTabs, terminal: &mut Terminal<B>) -> anyhow::Result<()>
 where
     B: Backend,
 {
     let mut draw_error = None;
     terminal.draw(|f| {
-        if let Err(error) = app.draw(f) {
+        if let Err(error) = tabs.draw(f) {
             draw_error = Some(error);
         }
     })?;
@@ -151,24 +296,24 @@ where
 }
 
 fn main_loop<B>(
-    app: &mut App,
+    tabs: &mut BrokerTabs,
     rx: &Receiver<Event>,
     terminal: &mut Terminal<B>,
 ) -> anyhow::Result<()>
 where
     B: Backend,
 {
-    terminal_draw(app, terminal)?;
+    terminal_draw(tabs, terminal)?;
     loop {
         let refresh = match rx.recv()? {
-            Event::Key(event) => app.on_key(event)?,
-            Event::MouseClick { column, row } => app.on_click(column, row)?,
-            Event::MouseScrollDown => app.on_down()?,
-            Event::MouseScrollUp => app.on_up()?,
-            Event::Tick => Refresh::Update,
+            Event::Key(event) => tabs.on_key(event)?,
+            Event::MouseClick { column, row } => tabs.selected_app().on_click(column, row)?,
+            Event::MouseScrollDown => tabs.selected_app().on_down()?,
+            Event::MouseScrollUp => tabs.selected_app().on_up()?,
+            Event::Tick => tabs.on_tick()?,
         };
         match refresh {
-            Refresh::Update => terminal_draw(app, terminal)?,
+            Refresh::Update => terminal_draw(tabs, terminal)?,
             Refresh::Skip => {}
             Refresh::Quit => break,
         }
@@ -176,205 +321,566 @@ where
     Ok(())
 }
 
+/// One [`App`] per broker of which the selected one is shown
+#[derive(Default)]
+struct BrokerTabs<'a> {
+    apps: Vec<App<'a>>,
+    titles: Vec<String>,
+    selected: usize,
+}
+
+impl<'a> BrokerTabs<'a> {
+    fn selected_app(&mut self) -> &mut App<'a> {
+        &mut self.apps[self.selected]
+    }
+
+    /// Regular maintenance of all brokers, not only the shown one
+    fn on_tick(&mut self) -> anyhow::Result<Refresh> {
+        let mut ring_bell = false;
+        for app in &mut self.apps {
+            if let Some(mqtt_thread) = &app.mqtt_thread {
+                mqtt_thread.drop_expired()?;
+                mqtt_thread.flush_history()?;
+                // The merged tab only repeats the alerts of the brokers
+                ring_bell |= app.check_alerts()?;
+            }
+        }
+        if ring_bell {
+            alerts::ring_bell();
+        }
+        Ok(Refresh::Update)
+    }
+
+    /// Write out everything still buffered as the mqtt threads are not joined on quit
+    fn flush_histories(&self) -> anyhow::Result<()> {
+        for mqtt_thread in self.apps.iter().filter_map(|app| app.mqtt_thread.as_ref()) {
+            mqtt_thread.flush_history()?;
+        }
+        Ok(())
+    }
+
+    fn on_key(&mut self, key: KeyEvent) -> anyhow::Result<Refresh> {
+        let app = &self.apps[self.selected];
+        // Text inputs have no pane with keys to switch
+        let action = app
+            .focused_pane()
+            .and_then(|pane| app.keys.action(pane, key))
+            .filter(|_| self.apps.len() > 1);
+        match action {
+            Some(Action::PreviousBroker) => {
+                self.selected = self.selected.checked_sub(1).unwrap_or(self.apps.len() - 1);
+                Ok(Refresh::Update)
+            }
+            Some(Action::NextBroker) => {
+                self.selected = (self.selected + 1) % self.apps.len();
+                Ok(Refresh::Update)
+            }
+            _ => self.selected_app().on_key(key),
+        }
+    }
+
+    fn draw<B>(&mut self, f: &mut Frame<B>) -> anyhow::Result<()>
+    where
+        B: Backend,
+    {
+        let area = f.size();
+        if self.apps.len() <= 1 {
+            return self.selected_app().draw(f, area);
+        }
+
+        let (tab_area, app_area) = split_area_vertically(area, 1);
+        let titles = self
+            .titles
+            .iter()
+            .zip(&self.apps)
+            .map(|(title, app)| {
+                let has_error = app.has_connection_err().map_or(true, |err| err.is_some());
+                let style = if has_error {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default()
+                };
+                Spans::from(Span::styled(title.as_str(), style))
+            })
+            .collect();
+        let widget = Tabs::new(titles)
+            .select(self.selected)
+            .highlight_style(Style::default().fg(Color::Black).bg(focus_color(true)))
+            .divider(" | ");
+        f.render_widget(widget, tab_area);
+        let keys = &self.apps[self.selected].keys;
+        let hint = format!(
+            "{} {} switch broker",
+            keys.hint(Action::PreviousBroker),
+            keys.hint(Action::NextBroker)
+        );
+        f.render_widget(
+            Paragraph::new(Span::styled(hint, STYLE_DARKGRAY)).alignment(Alignment::Right),
+            tab_area,
+        );
+
+        self.selected_app().draw(f, app_area)
+    }
+}
+
 struct App<'a> {
     details: details::Details,
     focus: ElementInFocus,
     info_header: info_header::InfoHeader,
-    mqtt_thread: mqtt_thread::MqttThread,
+    /// None for the merged view of all brokers
+    mqtt_thread: Option<mqtt_thread::MqttThread>,
+    /// Shown histories. The merged view has all of them, each named by its broker.
+    histories: Vec<(Option<String>, mqtt_thread::HistoryArc)>,
+    /// While paused the histories keep receiving but only show what was received until then.
+    /// The message and dropped totals at that time tell how many messages are new and how many were dropped meanwhile.
+    paused: Option<(DateTime<Local>, usize, usize)>,
     topic_overview: topic_overview::TopicOverview,
-    search_box: TextArea<'a>,
+    watchlist: watchlist::Watchlist,
+    alerts: alerts::AlertsPanel,
+    keys: KeyMap,
+    search: search::Search<'a>,
+    subscriptions: subscriptions::Subscriptions<'a>,
+    export: Option<export::Export<'a>>,
 }
 
 impl<'a> App<'a> {
-    fn new(broker: &Broker, mqtt_thread: mqtt_thread::MqttThread) -> Self {
+    fn new(
+        info_header: info_header::InfoHeader,
+        mqtt_thread: Option<mqtt_thread::MqttThread>,
+        histories: Vec<(Option<String>, mqtt_thread::HistoryArc)>,
+        auto_expand: &AutoExpand,
+        watchlist: watchlist::Watchlist,
+        alerting: &Alerting,
+        keys: &KeyMap,
+    ) -> Self {
         Self {
-            details: details::Details::default(),
+            details: details::Details::new(keys),
             focus: ElementInFocus::TopicOverview,
-            info_header: info_header::InfoHeader::new(broker),
+            info_header,
             mqtt_thread,
-            topic_overview: topic_overview::TopicOverview::default(),
-            search_box: TextArea::default(),
+            histories,
+            paused: None,
+            topic_overview: topic_overview::TopicOverview::new(auto_expand.clone()),
+            watchlist,
+            alerts: alerts::AlertsPanel::new(alerting),
+            keys: keys.clone(),
+            search: search::Search::default(),
+            subscriptions: subscriptions::Subscriptions::default(),
+            export: None,
         }
     }
 
+    const fn is_merged(&self) -> bool {
+        self.mqtt_thread.is_none()
+    }
+
+    fn get_histories(&self) -> anyhow::Result<mqtt_thread::HistoryGuards<'_>> {
+        mqtt_thread::read_histories(&self.histories, self.get_paused_time())
+    }
+
+    /// Notice new alerts even when the tab is not shown. Returns whether the bell should ring.
+    fn check_alerts(&mut self) -> anyhow::Result<bool> {
+        let guards = mqtt_thread::read_histories(&self.histories, self.get_paused_time())?;
+        Ok(self.alerts.check(&guards.view(), Local::now()))
+    }
+
+    fn get_paused_time(&self) -> Option<DateTime<Local>> {
+        self.paused.map(|(time, _, _)| time)
+    }
+
+    fn has_connection_err(&self) -> anyhow::Result<Option<String>> {
+        self.mqtt_thread
+            .as_ref()
+            .map_or(Ok(None), mqtt_thread::MqttThread::has_connection_err)
+    }
+
+    /// JSON of the entry shown in the payload pane
     fn get_json_of_current_topic(&self) -> anyhow::Result<Option<JsonValue>> {
         if let Some(topic) = self.topic_overview.get_selected() {
+            let guards = self.get_histories()?;
+            let history = guards.view();
             let json = self
-                .mqtt_thread
-                .get_history()?
-                .get_last(topic)
-                .and_then(|last| last.payload.as_optional_json().cloned());
+                .details
+                .selected_entry(topic, &history, self.is_merged())
+                .and_then(|entry| entry.payload.as_optional_json().cloned());
             Ok(json)
         } else {
             Ok(None)
         }
     }
 
-    fn search_for_word(&self, query: String) -> Option<HashSet<String>> {
-        if let Ok(historylocked) = self.mqtt_thread.get_history() {
-            return historylocked.search(&query);
+    /// JSON of the payload pane and the JSON it is compared with in the diff mode
+    fn get_json_and_diff_base(&self) -> anyhow::Result<(JsonValue, Option<JsonValue>)> {
+        let json = self.get_json_of_current_topic()?.unwrap_or(JsonValue::Null);
+        let guards = self.get_histories()?;
+        let history = guards.view();
+        let diff_base = self
+            .topic_overview
+            .get_selected()
+            .as_ref()
+            .and_then(|topic| {
+                self.details
+                    .diff_base(topic, &history, self.is_merged())
+                    .and_then(|entry| entry.payload.as_optional_json().cloned())
+            });
+        Ok((json, diff_base))
+    }
+
+    /// Topics shown in the overview, reduced to the search hits while searching
+    fn get_visible_topics(&mut self) -> anyhow::Result<Vec<String>> {
+        let guards = mqtt_thread::read_histories(&self.histories, self.get_paused_time())?;
+        let history = guards.view();
+        self.search.update_hits(&history);
+        Ok(history.get_visible_topics(
+            self.topic_overview.get_opened(),
+            self.search.get_hits(),
+            self.topic_overview.get_sort(),
+        ))
+    }
+
+    /// Freeze the shown history while it keeps receiving or show everything again.
+    /// The [`HistoryLimits`] still apply so entries of the frozen history might be dropped meanwhile.
+    fn toggle_pause(&mut self) -> anyhow::Result<()> {
+        if self.paused.take().is_none() {
+            let (message_total, dropped_total) = {
+                let guards = self.get_histories()?;
+                let history = guards.view();
+                (history.get_message_total(), history.get_dropped_total())
+            };
+            self.paused = Some((Local::now(), message_total, dropped_total));
+        }
+        Ok(())
+    }
+
+    /// The focused pane when its keys are configured by the [`KeyMap`]
+    const fn focused_pane(&self) -> Option<Pane> {
+        match self.focus {
+            ElementInFocus::TopicOverview => Some(Pane::Topics),
+            ElementInFocus::JsonPayload => Some(Pane::Payload),
+            ElementInFocus::HistoryTable => Some(Pane::History),
+            ElementInFocus::Watchlist => Some(Pane::Watchlist),
+            ElementInFocus::Graph => Some(Pane::Graph),
+            ElementInFocus::CleanRetainedPopup(_)
+            | ElementInFocus::SearchMode
+            | ElementInFocus::Subscriptions
+            | ElementInFocus::ExportPopup => None,
         }
-        None
+    }
+
+    fn change_selected_topic(&mut self, cursor_move: CursorMove) -> anyhow::Result<Refresh> {
+        let visible = self.get_visible_topics()?;
+        self.topic_overview.change_selected(&visible, cursor_move);
+        Ok(Refresh::Update)
     }
 
     #[allow(clippy::too_many_lines)]
-    fn on_key(&mut self, key: KeyEvent) -> anyhow::Result<Refresh> {
-        let refresh = match &self.focus {
-            ElementInFocus::TopicOverview => match key.code {
-                KeyCode::Char('q') => Refresh::Quit,
-                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    Refresh::Quit
+    fn on_action(&mut self, pane: Pane, action: Action) -> anyhow::Result<Refresh> {
+        let refresh = match (pane, action) {
+            (_, Action::Quit) => Refresh::Quit,
+            (_, Action::Pause) => {
+                self.toggle_pause()?;
+                Refresh::Update
+            }
+            (Pane::Topics | Pane::Payload | Pane::History | Pane::Watchlist, Action::Up) => {
+                self.on_up()?
+            }
+            (Pane::Topics | Pane::Payload | Pane::History | Pane::Watchlist, Action::Down) => {
+                self.on_down()?
+            }
+            (Pane::Topics, Action::NextPane) => {
+                let is_json_on_topic = self.get_json_of_current_topic()?.is_some();
+                if is_json_on_topic {
+                    self.focus = ElementInFocus::JsonPayload;
+                } else if self.has_history_of_current_topic()? {
+                    self.focus = ElementInFocus::HistoryTable;
                 }
-                KeyCode::Tab | KeyCode::BackTab => {
-                    let is_json_on_topic = self.get_json_of_current_topic()?.is_some();
-                    if is_json_on_topic {
-                        self.focus = ElementInFocus::JsonPayload;
-                    }
+                Refresh::Update
+            }
+            (Pane::Topics, Action::Toggle) => {
+                self.topic_overview.toggle();
+                Refresh::Update
+            }
+            (Pane::Topics, Action::Left) => {
+                self.topic_overview.close();
+                Refresh::Update
+            }
+            (Pane::Topics, Action::Right) => {
+                self.topic_overview.open();
+                Refresh::Update
+            }
+            (Pane::Topics, Action::CollapseAll) => {
+                self.topic_overview.collapse_all();
+                Refresh::Update
+            }
+            (Pane::Topics, Action::ExpandAll) => {
+                let guards = mqtt_thread::read_histories(&self.histories, self.get_paused_time())?;
+                self.topic_overview.expand_all(&guards.view());
+                Refresh::Update
+            }
+            (Pane::Topics, Action::First) => self.change_selected_topic(CursorMove::Absolute(0))?,
+            (Pane::Topics, Action::Last) => {
+                self.change_selected_topic(CursorMove::Absolute(usize::MAX))?
+            }
+            (Pane::Topics, Action::PageUp) => self.change_selected_topic(CursorMove::PageUp)?,
+            (Pane::Topics, Action::PageDown) => self.change_selected_topic(CursorMove::PageDown)?,
+            // Cleaning is done per broker and not in the merged view
+            (Pane::Topics, Action::Delete) if !self.is_merged() => {
+                if let Some(topic) = self.topic_overview.get_selected() {
+                    self.focus = ElementInFocus::CleanRetainedPopup(topic.to_string());
                     Refresh::Update
+                } else {
+                    Refresh::Skip
                 }
-                KeyCode::Enter | KeyCode::Char(' ') => {
-                    self.topic_overview.toggle();
-                    Refresh::Update
+            }
+            (Pane::Topics, Action::Search) => {
+                self.focus = ElementInFocus::SearchMode;
+                Refresh::Update
+            }
+            (Pane::Topics, Action::Watch) => {
+                if let Some(topic) = self.topic_overview.get_selected() {
+                    self.watchlist.toggle(topic);
                 }
-                KeyCode::Down | KeyCode::Char('j') => self.on_down()?,
-                KeyCode::Up | KeyCode::Char('k') => self.on_up()?,
-                KeyCode::Left | KeyCode::Char('h') => {
-                    self.topic_overview.close();
-                    Refresh::Update
+                Refresh::Update
+            }
+            (Pane::Topics, Action::Watchlist) if !self.watchlist.is_empty() => {
+                self.focus = ElementInFocus::Watchlist;
+                Refresh::Update
+            }
+            (Pane::Topics, Action::Back) if self.search.get_query().is_some() => {
+                self.search.clear();
+                Refresh::Update
+            }
+            (Pane::Topics, Action::Subscriptions) if !self.is_merged() => {
+                self.focus = ElementInFocus::Subscriptions;
+                Refresh::Update
+            }
+            (Pane::Topics, Action::Sort) => {
+                self.topic_overview.next_sort();
+                Refresh::Update
+            }
+            (Pane::Topics | Pane::Payload, Action::Export) => self.open_export()?,
+            (Pane::Topics | Pane::Payload, Action::Pin) => self.toggle_pin(false)?,
+            (Pane::Topics | Pane::Payload, Action::PinSecondAxis) => self.toggle_pin(true)?,
+            (Pane::Topics | Pane::Payload, Action::Graph) => self.focus_graph(),
+            (Pane::Payload, Action::NextPane) => {
+                self.focus = ElementInFocus::HistoryTable;
+                Refresh::Update
+            }
+            (Pane::Payload, Action::Toggle) => {
+                self.details.json_view.toggle_selected();
+                Refresh::Update
+            }
+            (Pane::Payload, Action::Left) => {
+                self.details.json_view.key_left();
+                Refresh::Update
+            }
+            (Pane::Payload, Action::Right) => {
+                self.details.json_view.key_right();
+                Refresh::Update
+            }
+            (Pane::Payload, Action::First) => {
+                self.details.json_view.select_first();
+                Refresh::Update
+            }
+            (Pane::Payload, Action::Last) => {
+                let (json, diff_base) = self.get_json_and_diff_base()?;
+                let items = json_view::tree_items(&json, diff_base.as_ref());
+                self.details.json_view.select_last(&items);
+                Refresh::Update
+            }
+            (Pane::Payload | Pane::History, Action::Diff) => {
+                self.details.toggle_diff();
+                Refresh::Update
+            }
+            (Pane::History, Action::Back | Action::NextPane)
+            | (Pane::Watchlist, Action::Back | Action::NextPane | Action::Watchlist)
+            | (Pane::Graph, Action::Back | Action::NextPane | Action::Graph) => {
+                self.focus = ElementInFocus::TopicOverview;
+                Refresh::Update
+            }
+            (Pane::History, Action::First) => {
+                self.change_selected_entry(CursorMove::Absolute(0))?
+            }
+            (Pane::History, Action::Last) => {
+                self.change_selected_entry(CursorMove::Absolute(usize::MAX))?
+            }
+            (Pane::History, Action::PageUp) => self.change_selected_entry(CursorMove::PageUp)?,
+            (Pane::History, Action::PageDown) => {
+                self.change_selected_entry(CursorMove::PageDown)?
+            }
+            (Pane::History, Action::Mark) => {
+                if let Some(topic) = self.topic_overview.get_selected() {
+                    let guards =
+                        mqtt_thread::read_histories(&self.histories, self.get_paused_time())?;
+                    self.details
+                        .toggle_mark(topic, &guards.view(), self.is_merged());
                 }
-                KeyCode::Right | KeyCode::Char('l') => {
-                    self.topic_overview.open();
-                    Refresh::Update
+                Refresh::Update
+            }
+            (Pane::Watchlist, Action::Toggle) => {
+                if let Some(topic) = self.watchlist.get_selected() {
+                    self.topic_overview.select_topic(&topic);
+                    self.focus = ElementInFocus::TopicOverview;
                 }
-                KeyCode::Home => {
-                    let visible = self.mqtt_thread.get_history()?.get_visible_topics(
-                        self.topic_overview.get_opened(),
-                        self.topic_overview.get_query_items(),
-                    );
-                    self.topic_overview
-                        .change_selected(&visible, CursorMove::Absolute(0));
-                    Refresh::Update
+                Refresh::Update
+            }
+            (Pane::Watchlist, Action::Watch | Action::Delete) => {
+                if let Some(topic) = self.watchlist.get_selected() {
+                    self.watchlist.toggle(&topic);
                 }
-                KeyCode::End => {
-                    let visible = self.mqtt_thread.get_history()?.get_visible_topics(
-                        self.topic_overview.get_opened(),
-                        self.topic_overview.get_query_items(),
-                    );
-                    self.topic_overview
-                        .change_selected(&visible, CursorMove::Absolute(usize::MAX));
-                    Refresh::Update
+                if self.watchlist.is_empty() {
+                    self.focus = ElementInFocus::TopicOverview;
                 }
-                KeyCode::PageUp => {
-                    let visible = self.mqtt_thread.get_history()?.get_visible_topics(
-                        self.topic_overview.get_opened(),
-                        self.topic_overview.get_query_items(),
-                    );
-                    self.topic_overview
-                        .change_selected(&visible, CursorMove::PageUp);
-                    Refresh::Update
+                Refresh::Update
+            }
+            (Pane::Graph, Action::TimeWindow) => {
+                self.details.graph_view.next_window();
+                Refresh::Update
+            }
+            (Pane::Graph, Action::ZoomIn) => {
+                self.details.graph_view.zoom_in();
+                Refresh::Update
+            }
+            (Pane::Graph, Action::ZoomOut) => {
+                self.details.graph_view.zoom_out();
+                Refresh::Update
+            }
+            (Pane::Graph, Action::PanBack) => {
+                self.details.graph_view.pan_back();
+                Refresh::Update
+            }
+            (Pane::Graph, Action::PanForward) => {
+                self.details.graph_view.pan_forward();
+                Refresh::Update
+            }
+            (Pane::Graph, Action::Left) => {
+                self.details.graph_view.cursor_previous();
+                Refresh::Update
+            }
+            (Pane::Graph, Action::Right) => {
+                self.details.graph_view.cursor_next();
+                Refresh::Update
+            }
+            (Pane::Graph, Action::Last) => {
+                self.details.graph_view.reset();
+                Refresh::Update
+            }
+            // Switching brokers is done by the BrokerTabs
+            _ => Refresh::Skip,
+        };
+        Ok(refresh)
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn on_key(&mut self, key: KeyEvent) -> anyhow::Result<Refresh> {
+        if let Some(pane) = self.focused_pane() {
+            return match self.keys.action(pane, key) {
+                Some(action) => self.on_action(pane, action),
+                None => Ok(Refresh::Skip),
+            };
+        }
+
+        let refresh = match &self.focus {
+            ElementInFocus::CleanRetainedPopup(topic) => {
+                if let (KeyCode::Enter | KeyCode::Char(' '), Some(mqtt_thread)) =
+                    (key.code, &mut self.mqtt_thread)
+                {
+                    mqtt_thread.clean_below(topic)?;
                 }
-                KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    let visible = self.mqtt_thread.get_history()?.get_visible_topics(
-                        self.topic_overview.get_opened(),
-                        self.topic_overview.get_query_items(),
-                    );
-                    self.topic_overview
-                        .change_selected(&visible, CursorMove::PageUp);
+                self.focus = ElementInFocus::TopicOverview;
+                Refresh::Update
+            }
+            ElementInFocus::SearchMode => match key.code {
+                KeyCode::Esc => {
+                    self.search.clear();
+                    self.focus = ElementInFocus::TopicOverview;
                     Refresh::Update
                 }
-                KeyCode::PageDown => {
-                    let visible = self.mqtt_thread.get_history()?.get_visible_topics(
-                        self.topic_overview.get_opened(),
-                        self.topic_overview.get_query_items(),
-                    );
-                    self.topic_overview
-                        .change_selected(&visible, CursorMove::PageDown);
+                KeyCode::Enter => {
+                    self.focus = ElementInFocus::TopicOverview;
                     Refresh::Update
                 }
-                KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    let visible = self.mqtt_thread.get_history()?.get_visible_topics(
-                        self.topic_overview.get_opened(),
-                        self.topic_overview.get_query_items(),
-                    );
-                    self.topic_overview
-                        .change_selected(&visible, CursorMove::PageDown);
+                KeyCode::Tab => {
+                    self.search.toggle_target();
                     Refresh::Update
                 }
-                KeyCode::Backspace | KeyCode::Delete => {
-                    if let Some(topic) = self.topic_overview.get_selected() {
-                        self.focus = ElementInFocus::CleanRetainedPopup(topic.to_string());
-                        Refresh::Update
-                    } else {
-                        Refresh::Skip
-                    }
+                KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.search.toggle_regex();
+                    Refresh::Update
                 }
-
-                KeyCode::Char('/') => {
-                    self.focus = ElementInFocus::SearchMode;
+                _ => {
+                    self.search.input(key);
                     Refresh::Update
                 }
-                _ => Refresh::Skip,
             },
-            ElementInFocus::JsonPayload => match key.code {
-                KeyCode::Char('q') => Refresh::Quit,
-                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    Refresh::Quit
-                }
-                KeyCode::Tab | KeyCode::BackTab => {
+            ElementInFocus::Subscriptions => match key.code {
+                KeyCode::Esc => {
                     self.focus = ElementInFocus::TopicOverview;
                     Refresh::Update
                 }
-                KeyCode::Enter | KeyCode::Char(' ') => {
-                    self.details.json_view.toggle_selected();
-                    Refresh::Update
-                }
-                KeyCode::Down | KeyCode::Char('j') => self.on_down()?,
-                KeyCode::Up | KeyCode::Char('k') => self.on_up()?,
-                KeyCode::Left | KeyCode::Char('h') => {
-                    self.details.json_view.key_left();
+                KeyCode::Enter => {
+                    if let (Some(filter), Some(mqtt_thread)) =
+                        (self.subscriptions.take_filter(), &mut self.mqtt_thread)
+                    {
+                        let qos = self.subscriptions.get_qos();
+                        mqtt_thread.subscribe(filter, qos)?;
+                    }
                     Refresh::Update
                 }
-                KeyCode::Right | KeyCode::Char('l') => {
-                    self.details.json_view.key_right();
+                KeyCode::Tab | KeyCode::BackTab => {
+                    self.subscriptions.next_qos();
                     Refresh::Update
                 }
-                KeyCode::Home => {
-                    self.details.json_view.select_first();
+                KeyCode::Delete => {
+                    if let Some(mqtt_thread) = &mut self.mqtt_thread {
+                        let selected = self
+                            .subscriptions
+                            .get_selected(mqtt_thread.get_subscriptions()?.get_subscriptions())
+                            .map(ToString::to_string);
+                        if let Some(filter) = selected {
+                            mqtt_thread.unsubscribe(&filter)?;
+                        }
+                    }
                     Refresh::Update
                 }
-                KeyCode::End => {
-                    let json = self.get_json_of_current_topic()?.unwrap_or(JsonValue::Null);
-                    let items = root_tree_items_from_json(&json);
-                    self.details.json_view.select_last(&items);
+                KeyCode::Down => self.on_down()?,
+                KeyCode::Up => self.on_up()?,
+                _ => {
+                    self.subscriptions.input(key);
                     Refresh::Update
                 }
-                _ => Refresh::Skip,
             },
-            ElementInFocus::CleanRetainedPopup(topic) => {
-                if matches!(key.code, KeyCode::Enter | KeyCode::Char(' ')) {
-                    self.mqtt_thread.clean_below(topic)?;
-                }
-                self.focus = ElementInFocus::TopicOverview;
-                Refresh::Update
-            }
-            ElementInFocus::SearchMode => match key.code {
-                KeyCode::Esc => {
-                    self.focus = ElementInFocus::TopicOverview;
-                    Refresh::Update
-                }
+            ElementInFocus::ExportPopup => match key.code {
+                KeyCode::Esc => self.close_export(),
                 KeyCode::Enter => {
-                    if let Some(temp) = &self.search_for_word(self.search_box.lines()[0].clone()) {
-                        self.topic_overview.set_opened(temp);
-                        self.focus = ElementInFocus::TopicOverview;
-                        return Ok(Refresh::Update);
+                    if self.export.as_ref().map_or(true, export::Export::is_done) {
+                        self.close_export()
+                    } else {
+                        self.export()?;
+                        Refresh::Update
                     }
-                    Refresh::Skip
                 }
+                KeyCode::Tab | KeyCode::BackTab => {
+                    if let Some(export) = &mut self.export {
+                        export.next_format();
+                    }
+                    Refresh::Update
+                }
+                KeyCode::Down => self.on_down()?,
+                KeyCode::Up => self.on_up()?,
                 _ => {
-                    self.search_box.input(key);
+                    if let Some(export) = &mut self.export {
+                        export.input(key);
+                    }
                     Refresh::Update
                 }
             },
+            // Handled by the KeyMap above
+            ElementInFocus::TopicOverview
+            | ElementInFocus::JsonPayload
+            | ElementInFocus::HistoryTable
+            | ElementInFocus::Watchlist
+            | ElementInFocus::Graph => Refresh::Skip,
         };
         Ok(refresh)
     }
@@ -382,20 +888,27 @@ impl<'a> App<'a> {
     fn on_up(&mut self) -> anyhow::Result<Refresh> {
         match self.focus {
             ElementInFocus::TopicOverview => {
-                let visible = self.mqtt_thread.get_history()?.get_visible_topics(
-                    self.topic_overview.get_opened(),
-                    self.topic_overview.get_query_items(),
-                );
+                let visible = self.get_visible_topics()?;
                 self.topic_overview
                     .change_selected(&visible, CursorMove::OneUp);
             }
             ElementInFocus::JsonPayload => {
-                let json = self.get_json_of_current_topic()?.unwrap_or(JsonValue::Null);
-                let items = root_tree_items_from_json(&json);
+                let (json, diff_base) = self.get_json_and_diff_base()?;
+                let items = json_view::tree_items(&json, diff_base.as_ref());
                 self.details.json_view.key_up(&items);
             }
             ElementInFocus::CleanRetainedPopup(_) => self.focus = ElementInFocus::TopicOverview,
-            ElementInFocus::SearchMode => {}
+            ElementInFocus::SearchMode | ElementInFocus::Graph => {}
+            ElementInFocus::HistoryTable => {
+                self.change_selected_entry(CursorMove::OneUp)?;
+            }
+            ElementInFocus::Subscriptions => self.subscriptions.select_previous(),
+            ElementInFocus::Watchlist => self.watchlist.select_previous(),
+            ElementInFocus::ExportPopup => {
+                if let Some(export) = &mut self.export {
+                    export.toggle_subtree();
+                }
+            }
         }
         Ok(Refresh::Update)
     }
@@ -403,30 +916,137 @@ impl<'a> App<'a> {
     fn on_down(&mut self) -> anyhow::Result<Refresh> {
         match self.focus {
             ElementInFocus::TopicOverview => {
-                let visible = self.mqtt_thread.get_history()?.get_visible_topics(
-                    self.topic_overview.get_opened(),
-                    self.topic_overview.get_query_items(),
-                );
+                let visible = self.get_visible_topics()?;
                 self.topic_overview
                     .change_selected(&visible, CursorMove::OneDown);
             }
             ElementInFocus::JsonPayload => {
-                let json = self.get_json_of_current_topic()?.unwrap_or(JsonValue::Null);
-                let items = root_tree_items_from_json(&json);
+                let (json, diff_base) = self.get_json_and_diff_base()?;
+                let items = json_view::tree_items(&json, diff_base.as_ref());
                 self.details.json_view.key_down(&items);
             }
             ElementInFocus::CleanRetainedPopup(_) => self.focus = ElementInFocus::TopicOverview,
-            ElementInFocus::SearchMode => {}
+            ElementInFocus::SearchMode | ElementInFocus::Graph => {}
+            ElementInFocus::HistoryTable => {
+                self.change_selected_entry(CursorMove::OneDown)?;
+            }
+            ElementInFocus::Subscriptions => {
+                let amount = match &self.mqtt_thread {
+                    Some(mqtt_thread) => mqtt_thread.get_subscriptions()?.get_subscriptions().len(),
+                    None => 0,
+                };
+                self.subscriptions.select_next(amount);
+            }
+            ElementInFocus::Watchlist => self.watchlist.select_next(),
+            ElementInFocus::ExportPopup => {
+                if let Some(export) = &mut self.export {
+                    export.toggle_subtree();
+                }
+            }
         }
         Ok(Refresh::Update)
     }
 
+    fn has_history_of_current_topic(&self) -> anyhow::Result<bool> {
+        Ok(match self.topic_overview.get_selected() {
+            Some(topic) => self.get_histories()?.view().get(topic).is_some(),
+            None => false,
+        })
+    }
+
+    fn change_selected_entry(&mut self, cursor_move: CursorMove) -> anyhow::Result<Refresh> {
+        if let Some(topic) = self.topic_overview.get_selected() {
+            let guards = mqtt_thread::read_histories(&self.histories, self.get_paused_time())?;
+            self.details.change_selected_entry(
+                topic,
+                &guards.view(),
+                self.is_merged(),
+                cursor_move,
+            );
+            Ok(Refresh::Update)
+        } else {
+            Ok(Refresh::Skip)
+        }
+    }
+
+    fn focus_graph(&mut self) -> Refresh {
+        if self.details.graph_view.is_shown() {
+            self.focus = ElementInFocus::Graph;
+            Refresh::Update
+        } else {
+            Refresh::Skip
+        }
+    }
+
+    /// Pin the selected value into the graph or move it to the other Y axis
+    fn toggle_pin(&mut self, y_axis: bool) -> anyhow::Result<Refresh> {
+        if let Some(topic) = self.topic_overview.get_selected().clone() {
+            let json = self.get_json_of_current_topic()?;
+            if y_axis {
+                self.details.toggle_y_axis(&topic, json.as_ref());
+            } else {
+                self.details.toggle_pin(&topic, json.as_ref());
+            }
+            Ok(Refresh::Update)
+        } else {
+            Ok(Refresh::Skip)
+        }
+    }
+
+    fn open_export(&mut self) -> anyhow::Result<Refresh> {
+        if let Some(topic) = self.topic_overview.get_selected() {
+            let has_history = self.get_histories()?.view().get(topic).is_some();
+            self.export = Some(export::Export::new(topic.clone(), has_history));
+            self.focus = ElementInFocus::ExportPopup;
+            Ok(Refresh::Update)
+        } else {
+            Ok(Refresh::Skip)
+        }
+    }
+
+    fn close_export(&mut self) -> Refresh {
+        self.export = None;
+        self.focus = ElementInFocus::TopicOverview;
+        Refresh::Update
+    }
+
+    /// Export the entries of the topic (and below) ordered by the time they were received
+    fn export(&mut self) -> anyhow::Result<()> {
+        let until = self.get_paused_time();
+        if let Some(export) = &mut self.export {
+            let guards = mqtt_thread::read_histories(&self.histories, until)?;
+            let history = guards.view();
+            let topics = if export.is_subtree() {
+                history.get_topics_below(export.get_topic())
+            } else {
+                vec![export.get_topic().to_string()]
+            };
+            let mut entries = topics
+                .iter()
+                .flat_map(|topic| {
+                    history
+                        .get(topic)
+                        .into_iter()
+                        .flatten()
+                        .map(move |entry| (topic.as_str(), entry))
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by_key(|(_, entry)| entry.received);
+
+            let result = export::to_file(
+                Path::new(export.get_path()),
+                export.get_format(),
+                &entries,
+                &self.details.json_view.selected(),
+            );
+            export.set_result(result);
+        }
+        Ok(())
+    }
+
     fn on_click(&mut self, column: u16, row: u16) -> anyhow::Result<Refresh> {
         if let Some(index) = self.topic_overview.index_of_click(column, row) {
-            let visible = self.mqtt_thread.get_history()?.get_visible_topics(
-                self.topic_overview.get_opened(),
-                self.topic_overview.get_query_items(),
-            );
+            let visible = self.get_visible_topics()?;
             let changed = self
                 .topic_overview
                 .change_selected(&visible, CursorMove::Absolute(index));
@@ -438,8 +1058,8 @@ impl<'a> App<'a> {
         }
 
         if let Some(index) = self.details.json_index_of_click(column, row) {
-            let json = self.get_json_of_current_topic()?.unwrap_or(JsonValue::Null);
-            let items = root_tree_items_from_json(&json);
+            let (json, diff_base) = self.get_json_and_diff_base()?;
+            let items = json_view::tree_items(&json, diff_base.as_ref());
             let opened = self.details.json_view.get_all_opened();
             let flattened = flatten(&opened, &items);
             if let Some(picked) = flattened.get(index) {
@@ -455,46 +1075,106 @@ impl<'a> App<'a> {
         Ok(Refresh::Skip)
     }
 
-    fn draw<B>(&mut self, f: &mut Frame<B>) -> anyhow::Result<()>
+    fn draw_info_header<B>(
+        &self,
+        f: &mut Frame<B>,
+        area: Rect,
+        history: &HistoryView,
+    ) -> anyhow::Result<()>
+    where
+        B: Backend,
+    {
+        let subscriptions = self
+            .mqtt_thread
+            .as_ref()
+            .map(mqtt_thread::MqttThread::get_subscriptions)
+            .transpose()?;
+        let rejected = subscriptions
+            .as_ref()
+            .map(|subscriptions| subscriptions.get_rejected())
+            .unwrap_or_default();
+        self.info_header.draw(
+            f,
+            area,
+            self.has_connection_err()?,
+            &rejected,
+            history.get_store_error(),
+            self.topic_overview.get_selected(),
+        );
+        Ok(())
+    }
+
+    /// Indicator at the right of the area while paused
+    fn draw_paused<B>(&self, f: &mut Frame<B>, area: Rect, history: &HistoryView)
+    where
+        B: Backend,
+    {
+        const STYLE: Style = Style {
+            fg: Some(Color::Black),
+            bg: Some(Color::Yellow),
+            add_modifier: Modifier::BOLD,
+            sub_modifier: Modifier::empty(),
+        };
+        if let Some((_, message_total, dropped_total)) = self.paused {
+            let new_messages = history.get_message_total().saturating_sub(message_total);
+            let dropped = history.get_dropped_total().saturating_sub(dropped_total);
+            let dropped = if dropped > 0 {
+                format!(", {dropped} dropped by the history limits")
+            } else {
+                String::new()
+            };
+            let text = format!(
+                " PAUSED, {new_messages} new messages{dropped} ({} to resume) ",
+                self.keys.hint(Action::Pause)
+            );
+            f.render_widget(
+                Paragraph::new(Span::styled(text, STYLE)).alignment(Alignment::Right),
+                area,
+            );
+        }
+    }
+
+    fn draw<B>(&mut self, f: &mut Frame<B>, area: Rect) -> anyhow::Result<()>
     where
         B: Backend,
     {
-        let area = f.size();
         let Rect { width, height, .. } = area;
         debug_assert_eq!(area.x, 0);
-        debug_assert_eq!(area.y, 0);
-        let header_area = Rect {
-            height: 2,
-            y: 0,
-            ..area
-        };
+        let header_area = Rect { height: 2, ..area };
         let main_area = Rect {
             height: height - 3,
-            y: 2,
+            y: area.y + 2,
             ..area
         };
         let key_hint_area = Rect {
             height: 1,
-            y: height - 1,
+            y: area.y + height - 1,
             ..area
         };
 
-        self.info_header.draw(
-            f,
-            header_area,
-            self.mqtt_thread.has_connection_err().unwrap(),
-            self.topic_overview.get_selected(),
-        );
-        draw_key_hints(&self, f, key_hint_area, &self.focus);
-
-        let history = self.mqtt_thread.get_history()?;
+        let guards = mqtt_thread::read_histories(&self.histories, self.get_paused_time())?;
+        let history = guards.view();
+        // Everything depending on the current time stays frozen while paused
+        let now = self.get_paused_time().unwrap_or_else(Local::now);
+        self.draw_info_header(f, header_area, &history)?;
+        self.search.update_hits(&history);
+        let search_hits = self.search.get_hits();
+        if matches!(self.focus, ElementInFocus::SearchMode) {
+            let hits = search_hits.map_or(0, TopicSet::len);
+            self.search.draw(f, key_hint_area, hits);
+        } else {
+            draw_key_hints(self, f, key_hint_area, &self.focus);
+        }
+        self.draw_paused(f, key_hint_area, &history);
 
-        let overview_area = self
-            .topic_overview
-            .get_selected()
-            .as_ref()
-            .and_then(|selected_topic| history.get(selected_topic))
-            .map_or(main_area, |topic_history| {
+        let selected_topic = self.topic_overview.get_selected().as_ref();
+        // The merged view compares the selected topic with the same topic on the other brokers
+        let compare_brokers = self.is_merged();
+        // Only remembered again when the details draw a graph
+        self.details.graph_view.remember_drawn(None, Vec::new());
+        let overview_area = selected_topic
+            .filter(|selected_topic| history.get(selected_topic).is_some())
+            .map_or(main_area, |selected_topic| {
                 let x = width / 3;
                 let details_area = Rect {
                     width: width - x,
@@ -505,8 +1185,11 @@ impl<'a> App<'a> {
                 self.details.draw(
                     f,
                     details_area,
-                    topic_history,
-                    matches!(self.focus, ElementInFocus::JsonPayload),
+                    selected_topic,
+                    &history,
+                    compare_brokers,
+                    &self.focus,
+                    now,
                 );
 
                 Rect {
@@ -516,25 +1199,50 @@ impl<'a> App<'a> {
                 }
             });
 
-        let (topic_amount, tree_items) =
-            history.to_tree_items(self.topic_overview.get_query_items());
-        self.topic_overview.ensure_state(&history);
+        let (overview_area, alerting) = alerts::AlertsPanel::draw(f, overview_area, &history);
+        let overview_area = self.watchlist.draw(
+            f,
+            overview_area,
+            &history,
+            matches!(self.focus, ElementInFocus::Watchlist),
+        );
+
+        self.topic_overview.auto_expand(&history);
+        self.topic_overview.mark_seen(&history);
+        let (topic_amount, tree_items) = history.to_tree_items(
+            &self
+                .topic_overview
+                .tree_items_view(search_hits, &alerting, now),
+        );
+        self.topic_overview.ensure_state(&history, search_hits);
         self.topic_overview.draw(
             f,
             overview_area,
             topic_amount,
             &tree_items,
+            self.search.get_filter_text(),
             matches!(self.focus, ElementInFocus::TopicOverview),
         );
         drop(history);
+        drop(guards);
 
         if let ElementInFocus::CleanRetainedPopup(topic) = &self.focus {
             clean_retained::draw_popup(f, topic);
         }
+        if let (ElementInFocus::ExportPopup, Some(export)) = (&self.focus, &mut self.export) {
+            export.draw(f);
+        }
+        if let (ElementInFocus::Subscriptions, Some(mqtt_thread)) = (&self.focus, &self.mqtt_thread)
+        {
+            let subscriptions = mqtt_thread.get_subscriptions()?;
+            self.subscriptions
+                .draw(f, subscriptions.get_subscriptions());
+        }
         Ok(())
     }
 }
 
+#[allow(clippy::too_many_lines)]
 fn draw_key_hints<B>(app: &App, f: &mut Frame<B>, area: Rect, focus: &ElementInFocus)
 where
     B: Backend,
@@ -545,39 +1253,139 @@ where
         add_modifier: Modifier::BOLD,
         sub_modifier: Modifier::empty(),
     };
-    if let ElementInFocus::SearchMode = focus {
-        f.render_widget(app.search_box.widget(), area);
-        return;
-    } else {
-        f.render_widget(
-            Paragraph::new(Spans::from(match focus {
-                ElementInFocus::TopicOverview => vec![
-                    Span::styled("q", STYLE),
-                    Span::from(" Quit  "),
-                    Span::styled("Tab", STYLE),
-                    Span::from(" Switch to JSON Payload  "),
-                    Span::styled("Del", STYLE),
-                    Span::from(" Clean retained  "),
-                    Span::styled(" / ", STYLE),
-                    Span::from(" Search"),
-                ],
-                ElementInFocus::JsonPayload => vec![
-                    Span::styled("q", STYLE),
-                    Span::from(" Quit  "),
-                    Span::styled("Tab", STYLE),
-                    Span::from(" Switch to Topics  "),
-                ],
-                ElementInFocus::CleanRetainedPopup(_) => vec![
-                    Span::styled("Enter", STYLE),
-                    Span::from(" Clean topic tree  "),
-                    Span::styled("Any", STYLE),
-                    Span::from(" Abort  "),
-                ],
-                _ => {
-                    vec![]
-                }
-            })),
-            area,
-        );
-    }
+    // The effective keys of the actions, hidden when none of them is bound
+    let hint = |actions: &[Action], label: &str| {
+        let keys = actions
+            .iter()
+            .map(|action| app.keys.hint(*action))
+            .collect::<String>();
+        if keys.is_empty() {
+            Vec::new()
+        } else {
+            vec![Span::styled(keys, STYLE), Span::from(format!(" {label}  "))]
+        }
+    };
+    f.render_widget(
+        Paragraph::new(Spans::from(match focus {
+            ElementInFocus::TopicOverview if app.is_merged() => [
+                hint(&[Action::Quit], "Quit"),
+                hint(&[Action::NextPane], "Switch to JSON Payload"),
+                hint(&[Action::Search], "Search"),
+                hint(&[Action::Watch], "Watch"),
+                hint(&[Action::Sort], "Sort"),
+                hint(&[Action::Pause], "Pause"),
+                hint(&[Action::Export], "Export"),
+            ]
+            .concat(),
+            ElementInFocus::TopicOverview => [
+                hint(&[Action::Quit], "Quit"),
+                hint(&[Action::NextPane], "Switch to JSON Payload"),
+                hint(&[Action::Delete], "Clean retained"),
+                hint(&[Action::Search], "Search"),
+                hint(&[Action::Watch], "Watch"),
+                hint(&[Action::Sort], "Sort"),
+                hint(&[Action::Pause], "Pause"),
+                hint(&[Action::Subscriptions], "Subscriptions"),
+                hint(&[Action::Export], "Export"),
+            ]
+            .concat(),
+            ElementInFocus::JsonPayload => [
+                hint(&[Action::Quit], "Quit"),
+                hint(&[Action::NextPane], "Switch to History"),
+                hint(&[Action::Export], "Export with the selected value"),
+                hint(&[Action::Pin], "Pin to graph"),
+                hint(&[Action::PinSecondAxis], "Second Y axis"),
+                hint(&[Action::Graph], "Graph"),
+                hint(&[Action::Diff], "Diff"),
+            ]
+            .concat(),
+            ElementInFocus::HistoryTable => [
+                hint(&[Action::Quit], "Quit"),
+                hint(&[Action::NextPane], "Switch to Topics"),
+                hint(&[Action::Up, Action::Down], "Show older message"),
+                hint(&[Action::Last], "Follow newest"),
+                hint(&[Action::Diff], "Diff"),
+                hint(&[Action::Mark], "Mark to diff with"),
+            ]
+            .concat(),
+            ElementInFocus::Graph => [
+                hint(&[Action::Back], "Back"),
+                hint(&[Action::TimeWindow], "Time window"),
+                hint(&[Action::ZoomIn, Action::ZoomOut], "Zoom"),
+                hint(&[Action::PanBack, Action::PanForward], "Pan"),
+                hint(&[Action::Left, Action::Right], "Cursor"),
+                hint(&[Action::Last], "Latest"),
+            ]
+            .concat(),
+            ElementInFocus::Watchlist => [
+                hint(&[Action::Quit], "Quit"),
+                hint(&[Action::Back], "Back to Topics"),
+                hint(&[Action::Toggle], "Jump to topic"),
+                hint(&[Action::Delete], "Unwatch"),
+            ]
+            .concat(),
+            ElementInFocus::CleanRetainedPopup(_) => vec![
+                Span::styled("Enter", STYLE),
+                Span::from(" Clean topic tree  "),
+                Span::styled("Any", STYLE),
+                Span::from(" Abort  "),
+            ],
+            ElementInFocus::Subscriptions => vec![
+                Span::styled("Esc", STYLE),
+                Span::from(" Close  "),
+                Span::styled("Enter", STYLE),
+                Span::from(" Subscribe  "),
+                Span::styled("Tab", STYLE),
+                Span::from(" Change QoS  "),
+                Span::styled("Del", STYLE),
+                Span::from(" Unsubscribe selected  "),
+            ],
+            ElementInFocus::ExportPopup => vec![
+                Span::styled("Esc", STYLE),
+                Span::from(" Close  "),
+                Span::styled("Enter", STYLE),
+                Span::from(" Export  "),
+                Span::styled("Tab", STYLE),
+                Span::from(" Change format  "),
+                Span::styled("↑↓", STYLE),
+                Span::from(" Change scope  "),
+            ],
+            ElementInFocus::SearchMode => {
+                vec![]
+            }
+        })),
+        area,
+    );
+}
+
+#[test]
+#[cfg(feature = "tls")]
+fn tree_labels_differ_by_scheme_and_path() {
+    let label = |broker: &str| tree_label(&broker.parse().unwrap());
+    assert_eq!(label("mqtt://localhost"), "mqtt:localhost:1883");
+    assert_ne!(
+        label("mqtt://localhost:8883"),
+        label("mqtts://localhost:8883")
+    );
+    assert_eq!(
+        label("ws://localhost:9001/mqtt"),
+        "ws:localhost:9001%2Fmqtt"
+    );
+    assert_ne!(label("ws://localhost/a"), label("ws://localhost/b"));
+    assert_ne!(label("ws://localhost/a/b"), label("ws://localhost/a%2Fb"));
+}
+
+#[test]
+#[cfg(feature = "tls")]
+fn history_file_names_differ_by_url() {
+    let file_name = |broker: &str| {
+        let broker = broker.parse().unwrap();
+        history_file_name(&tree_label(&broker), &broker)
+    };
+    let name = file_name("ws://localhost/a:b");
+    assert!(name.starts_with("ws_localhost_80_a_b-"));
+    assert!(name.ends_with(".mqttui"));
+    assert_ne!(name, file_name("ws://localhost/a_b"));
+    assert_eq!(name, file_name("ws://localhost/a:b"));
+    assert!(!file_name("ws://localhost/a\\b?c*").contains(['\\', '?', '*']));
 }
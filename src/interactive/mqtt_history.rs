@@ -1,16 +1,31 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{vec_deque, BTreeMap, HashMap, HashSet, VecDeque};
 
-use chrono::{DateTime, Local};
-use ego_tree::iter::Edge;
+use chrono::{DateTime, Duration, Local};
 use ego_tree::{NodeId, NodeRef, Tree};
 use rumqttc::Publish;
 use tui::style::{Color, Modifier, Style};
 use tui::text::{Span, Spans};
 use tui_tree_widget::{TreeIdentifierVec, TreeItem};
 
-use crate::interactive::ui::STYLE_BOLD;
-use crate::mqtt::{HistoryEntry, Payload};
+use crate::cli::{AlertCondition, AlertRule};
+use crate::interactive::alerts::{is_fulfilled, Alert};
+use crate::interactive::search::Query;
+use crate::interactive::ui::{STYLE_ALERT, STYLE_BOLD};
+use crate::mqtt::topic::{topic_filter_matches, TopicSet};
+use crate::mqtt::{capture, HistoryEntry, Payload};
 
+const STYLE_UPDATED: Style = Style {
+    fg: Some(Color::LightYellow),
+    bg: None,
+    add_modifier: Modifier::empty(),
+    sub_modifier: Modifier::empty(),
+};
+const STYLE_UNSEEN: Style = Style {
+    fg: Some(Color::Cyan),
+    bg: None,
+    add_modifier: Modifier::empty(),
+    sub_modifier: Modifier::empty(),
+};
 pub const STYLE_DARKGRAY: Style = Style {
     fg: Some(Color::DarkGray),
     bg: None,
@@ -18,42 +33,424 @@ pub const STYLE_DARKGRAY: Style = Style {
     sub_modifier: Modifier::empty(),
 };
 
+#[derive(Clone)]
 struct Topic {
     /// Topic `foo/bar` would have the leaf `bar`
     leaf: Box<str>,
-    history: Vec<HistoryEntry>,
+    /// Ring buffer of the entries, the oldest are dropped first
+    history: VecDeque<HistoryEntry>,
+    /// Amount of entries dropped because of the [`HistoryLimits`]
+    dropped: usize,
+    /// Kept for the silence alerts as the entries might be dropped already
+    last_received: Option<DateTime<Local>>,
+    /// Topics created after the cutoff of a [`HistoryView`] are not shown by it
+    created: DateTime<Local>,
 }
 
 impl Topic {
-    const fn new(leaf: Box<str>) -> Self {
+    fn new(leaf: Box<str>, created: DateTime<Local>) -> Self {
         Self {
             leaf,
-            history: Vec::new(),
+            history: VecDeque::new(),
+            dropped: 0,
+            last_received: None,
+            created,
+        }
+    }
+
+    fn drop_oldest(&mut self) -> Option<HistoryEntry> {
+        let entry = self.history.pop_front()?;
+        self.dropped += 1;
+        Some(entry)
+    }
+}
+
+/// Limits of the history after which the oldest entries are dropped
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HistoryLimits {
+    pub entries_per_topic: Option<usize>,
+    pub age: Option<Duration>,
+    /// Estimated memory usage of all entries in bytes
+    pub memory: Option<usize>,
+}
+
+impl HistoryLimits {
+    /// The limits over all topics need to know the order in which the entries arrived
+    const fn needs_arrivals(&self) -> bool {
+        self.age.is_some() || self.memory.is_some()
+    }
+}
+
+/// Entry of a topic in the order all entries arrived
+struct Arrival {
+    id: NodeId,
+    /// Index of the entry including the already dropped ones of the topic
+    index: usize,
+    time: DateTime<Local>,
+}
+
+/// Estimate of the memory used by the entry
+fn estimated_size(entry: &HistoryEntry) -> usize {
+    // The payload is kept raw and, when it is UTF-8, parsed
+    let parsed = match entry.payload {
+        Payload::NotUtf8(_) => 0,
+        Payload::String(_) | Payload::Json(_) => entry.payload_size,
+    };
+    std::mem::size_of::<HistoryEntry>() + parsed + entry.raw.len()
+}
+
+/// Order of the topics in the overview. Topics are ranked by all the topics below them including themselves.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Alphabetical,
+    RecentlyUpdated,
+    MostMessages,
+    MessageRate,
+    PayloadSize,
+}
+
+impl SortOrder {
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Alphabetical => Self::RecentlyUpdated,
+            Self::RecentlyUpdated => Self::MostMessages,
+            Self::MostMessages => Self::MessageRate,
+            Self::MessageRate => Self::PayloadSize,
+            Self::PayloadSize => Self::Alphabetical,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Alphabetical => "alphabetical",
+            Self::RecentlyUpdated => "recently updated",
+            Self::MostMessages => "most messages",
+            Self::MessageRate => "message rate",
+            Self::PayloadSize => "payload size",
+        }
+    }
+
+    /// Higher ranks are shown first
+    #[allow(clippy::cast_precision_loss)]
+    fn rank(self, node: Node) -> f64 {
+        let histories = node.descendants().map(Node::entries);
+        match self {
+            Self::Alphabetical => 0.0,
+            Self::RecentlyUpdated => histories
+                .filter_map(Entries::back)
+                .map(|entry| entry.received.timestamp_millis() as f64)
+                .fold(f64::NEG_INFINITY, f64::max),
+            Self::MostMessages => histories.map(Entries::len).sum::<usize>() as f64,
+            Self::MessageRate => histories.map(message_rate).sum(),
+            Self::PayloadSize => histories
+                .filter_map(Entries::back)
+                .map(|entry| entry.payload_size as f64)
+                .fold(0.0, f64::max),
+        }
+    }
+
+    /// Sort the alphabetically ordered nodes in this order. Equal ranks stay alphabetical.
+    fn sorted(self, nodes: Vec<Node>) -> Vec<Node> {
+        if self == Self::Alphabetical {
+            return nodes;
+        }
+        let ranks = nodes
+            .iter()
+            .map(|node| self.rank(*node))
+            .collect::<Vec<_>>();
+        let mut order = (0..nodes.len()).collect::<Vec<_>>();
+        order.sort_by(|a, b| ranks[*b].total_cmp(&ranks[*a]));
+        order.into_iter().map(|index| nodes[index]).collect()
+    }
+
+    /// Children of the node in this order
+    fn children(self, node: Node) -> Vec<Node> {
+        self.sorted(node.children().collect())
+    }
+}
+
+/// Messages per second between the first and the last kept message of the topic
+#[allow(clippy::cast_precision_loss)]
+fn message_rate(history: Entries) -> f64 {
+    match (history.front(), history.back()) {
+        (Some(first), Some(last)) if history.len() > 1 => {
+            let milliseconds = (last.received - first.received).num_milliseconds().max(1);
+            (history.len() - 1) as f64 * 1000.0 / milliseconds as f64
+        }
+        _ => 0.0,
+    }
+}
+
+/// Node of the shown tree. The merged view shows the root of each broker as a top-level node.
+#[derive(Clone, Copy)]
+struct Node<'a> {
+    tree_ref: NodeRef<'a, Topic>,
+    /// Name of the broker when this is its root
+    broker: Option<&'a str>,
+    /// See [`HistoryView::until`]
+    until: Option<DateTime<Local>>,
+}
+
+impl<'a> Node<'a> {
+    const fn topic(node: NodeRef<'a, Topic>, until: Option<DateTime<Local>>) -> Self {
+        Self {
+            tree_ref: node,
+            broker: None,
+            until,
         }
     }
+
+    /// Whether the topic was created before the cutoff
+    fn is_shown(self) -> bool {
+        self.until
+            .map_or(true, |until| self.tree_ref.value().created <= until)
+    }
+
+    fn entries(self) -> Entries<'a> {
+        let history = &self.tree_ref.value().history;
+        let len = self.until.map_or(history.len(), |until| {
+            history.partition_point(|entry| entry.received <= until)
+        });
+        Entries { history, len }
+    }
+
+    fn descendants(self) -> impl Iterator<Item = Node<'a>> {
+        let until = self.until;
+        self.tree_ref
+            .descendants()
+            .map(move |node| Self::topic(node, until))
+    }
+
+    fn leaf(self) -> &'a str {
+        self.broker.unwrap_or(&self.tree_ref.value().leaf)
+    }
+
+    fn value(self) -> &'a Topic {
+        self.tree_ref.value()
+    }
+
+    fn children(self) -> impl Iterator<Item = Node<'a>> {
+        let until = self.until;
+        self.tree_ref
+            .children()
+            .map(move |node| Self::topic(node, until))
+            .filter(|node| node.is_shown())
+    }
+}
+
+/// Entries of a topic up to the cutoff of the [`HistoryView`], oldest first
+#[derive(Clone, Copy)]
+pub struct Entries<'a> {
+    history: &'a VecDeque<HistoryEntry>,
+    len: usize,
+}
+
+impl<'a> Entries<'a> {
+    pub const fn len(self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.len == 0
+    }
+
+    pub fn front(self) -> Option<&'a HistoryEntry> {
+        self.iter().next()
+    }
+
+    pub fn back(self) -> Option<&'a HistoryEntry> {
+        self.iter().next_back()
+    }
+
+    pub fn iter(self) -> vec_deque::Iter<'a, HistoryEntry> {
+        self.history.range(..self.len)
+    }
+}
+
+impl<'a> From<&'a VecDeque<HistoryEntry>> for Entries<'a> {
+    fn from(history: &'a VecDeque<HistoryEntry>) -> Self {
+        Self {
+            history,
+            len: history.len(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for Entries<'a> {
+    type Item = &'a HistoryEntry;
+    type IntoIter = vec_deque::Iter<'a, HistoryEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 
 struct RecursiveTreeItemGenerator<'a> {
     messages_below: usize,
     messages: usize,
     topics_below: usize,
+    /// Newest message of the topic or any topic below it
+    last_received: Option<DateTime<Local>>,
     tree_item: TreeItem<'a>,
 }
 
+/// What is shown in the [`TreeItem`]s besides the topics and their latest payload
+pub struct TreeItemsView<'a> {
+    /// While searching only the hits with the topics above and below them are shown
+    pub hits: Option<&'a TopicSet>,
+    /// Alerting topics and the topics above them are highlighted
+    pub alerting: &'a TopicSet,
+    pub sort: SortOrder,
+    pub opened: &'a HashSet<String>,
+    pub selected: Option<&'a str>,
+    /// Message count of the topics when they were selected the last time, see [`HistoryView::get_message_count`]
+    pub seen: &'a HashMap<String, usize>,
+    pub now: DateTime<Local>,
+}
+
+/// Topics updated recently are highlighted. The highlight fades until it is gone after the last step.
+fn recently_updated_style(updated: DateTime<Local>, now: DateTime<Local>) -> Option<Style> {
+    const STEPS: [(i64, Style); 3] = [
+        (
+            1000,
+            Style {
+                add_modifier: Modifier::BOLD,
+                ..STYLE_UPDATED
+            },
+        ),
+        (3000, STYLE_UPDATED),
+        (
+            6000,
+            Style {
+                fg: Some(Color::Yellow),
+                ..STYLE_UPDATED
+            },
+        ),
+    ];
+    let milliseconds = (now - updated).num_milliseconds();
+    STEPS
+        .iter()
+        .find(|(max, _)| milliseconds < *max)
+        .map(|(_, style)| *style)
+}
+
 pub struct MqttHistory {
     tree: Tree<Topic>,
     ids: HashMap<String, NodeId>,
+    limits: HistoryLimits,
+    /// Only tracked when the [`HistoryLimits`] need them
+    arrivals: VecDeque<Arrival>,
+    /// Sum of the [`estimated_size`] of all entries
+    memory: usize,
+    /// Every added packet is also written to it, see [`Self::persist_to`]
+    store: Option<capture::Writer>,
+    store_error: Option<String>,
+    alert_rules: Vec<AlertRule>,
+    /// Since when the topic fulfills the value condition of the rule with the index
+    value_alerts: BTreeMap<(String, usize), DateTime<Local>>,
+    /// Amount of messages added including the dropped ones
+    message_total: usize,
+    /// Amount of entries dropped because of the [`HistoryLimits`] over all topics
+    dropped_total: usize,
+    /// Increases with every added or dropped entry to notice changes without comparing the entries
+    revision: usize,
 }
 
 impl MqttHistory {
+    #[cfg(test)]
     pub fn new() -> Self {
+        Self::with_limits(HistoryLimits::default())
+    }
+
+    pub fn with_limits(limits: HistoryLimits) -> Self {
         Self {
-            tree: Tree::new(Topic::new("".into())),
+            tree: Tree::new(Topic::new("".into(), Local::now())),
             ids: HashMap::new(),
+            limits,
+            arrivals: VecDeque::new(),
+            memory: 0,
+            store: None,
+            store_error: None,
+            alert_rules: Vec::new(),
+            value_alerts: BTreeMap::new(),
+            message_total: 0,
+            dropped_total: 0,
+            revision: 0,
         }
     }
 
-    fn entry(&mut self, topic: &str) -> NodeId {
+    /// Read access to the topics of this history
+    pub fn view(&self) -> HistoryView<'_> {
+        HistoryView::new(vec![(None, self)], None)
+    }
+
+    pub const fn get_message_total(&self) -> usize {
+        self.message_total
+    }
+
+    pub const fn get_dropped_total(&self) -> usize {
+        self.dropped_total
+    }
+
+    /// Evaluate the rules for every packet added from now on
+    pub fn set_alert_rules(&mut self, rules: Vec<AlertRule>) {
+        self.alert_rules = rules;
+        self.value_alerts.clear();
+    }
+
+    /// Persist every packet added from now on
+    pub fn persist_to(&mut self, store: capture::Writer) {
+        self.store = Some(store);
+        self.store_error = None;
+    }
+
+    /// Write the buffered packets to the file so they are not lost when mqttui is killed
+    pub fn flush_store(&mut self) {
+        self.with_store(capture::Writer::flush);
+    }
+
+    fn with_store(&mut self, action: impl FnOnce(&mut capture::Writer) -> std::io::Result<()>) {
+        if let Some(store) = &mut self.store {
+            if let Err(err) = action(store) {
+                self.store_error = Some(err.to_string());
+                self.store = None;
+            }
+        }
+    }
+
+    /// Persisting stops on the first error which is returned here
+    pub fn get_store_error(&self) -> Option<&str> {
+        self.store_error.as_deref()
+    }
+
+    /// The kept entries as packets in the order they were received, see [`capture::rewrite`]
+    pub fn records(&self) -> Vec<(Publish, DateTime<Local>)> {
+        let mut records = self
+            .ids
+            .iter()
+            .flat_map(|(topic, id)| {
+                let history = &self.tree.get(*id).unwrap().value().history;
+                history
+                    .iter()
+                    .map(move |entry| (entry.to_packet(topic), entry.received))
+            })
+            .collect::<Vec<_>>();
+        records.sort_by_key(|(_, time)| *time);
+        records
+    }
+
+    /// Node of the topic, also the ones without entries
+    fn node(&self, topic: &str) -> Option<NodeRef<'_, Topic>> {
+        if let Some(id) = self.ids.get(topic) {
+            return self.tree.get(*id);
+        }
+        topic.split('/').try_fold(self.tree.root(), |node, part| {
+            node.children().find(|o| &*o.value().leaf == part)
+        })
+    }
+
+    fn entry(&mut self, topic: &str, time: DateTime<Local>) -> NodeId {
         if let Some(id) = self.ids.get(topic) {
             *id
         } else {
@@ -68,10 +465,10 @@ impl MqttHistory {
                     }
                     let eoa_id = eoa.id();
                     let mut eoamut = self.tree.get_mut(eoa_id).unwrap();
-                    parent = eoamut.insert_before(Topic::new(part.into())).id();
+                    parent = eoamut.insert_before(Topic::new(part.into(), time)).id();
                 } else {
                     let mut nodemut = self.tree.get_mut(parent).unwrap();
-                    parent = nodemut.append(Topic::new(part.into())).id();
+                    parent = nodemut.append(Topic::new(part.into(), time)).id();
                 }
             }
             self.ids.insert(topic.to_string(), parent);
@@ -79,131 +476,480 @@ impl MqttHistory {
         }
     }
 
-    pub fn search(&self, search_word: &str) -> Option<HashSet<String>> {
-        let mut imei = String::new();
-        let mut res_vector = Vec::new();
-        for i in self.tree.root().traverse() {
-            match i {
-                Edge::Open(topic) => {
-                    if (*topic.value().leaf).contains(search_word) {
-                        imei = topic.value().leaf.to_string();
-                        res_vector.push(topic);
+    pub fn add(&mut self, packet: &Publish, time: DateTime<Local>) {
+        self.with_store(|store| store.write(packet, time));
+        self.push(&packet.topic, HistoryEntry::new(packet, time), time);
+    }
+
+    /// Add the entry and drop the oldest ones exceeding the [`HistoryLimits`]
+    fn push(&mut self, topic: &str, entry: HistoryEntry, time: DateTime<Local>) {
+        self.evaluate_alerts(topic, &entry.payload, time);
+        self.message_total += 1;
+        self.revision += 1;
+        let id = self.entry(topic, time);
+        self.memory += estimated_size(&entry);
+
+        let mut node = self.tree.get_mut(id).unwrap();
+        let topic = node.value();
+        let index = topic.dropped + topic.history.len();
+        topic.last_received = Some(entry.received);
+        topic.history.push_back(entry);
+        self.drop_over_topic_limit(id);
+
+        if self.limits.needs_arrivals() {
+            self.arrivals.push_back(Arrival { id, index, time });
+            self.drop_over_limits(time);
+        }
+    }
+
+    fn evaluate_alerts(&mut self, topic: &str, payload: &Payload, time: DateTime<Local>) {
+        for (index, rule) in self.alert_rules.iter().enumerate() {
+            if matches!(rule.condition, AlertCondition::Silent(_))
+                || !topic_filter_matches(&rule.filter, topic)
+            {
+                continue;
+            }
+            let key = (topic.to_string(), index);
+            if is_fulfilled(&rule.condition, payload) {
+                self.value_alerts.entry(key).or_insert(time);
+            } else {
+                self.value_alerts.remove(&key);
+            }
+        }
+    }
+
+    /// Active alerts, oldest first. Silence is checked against the given time.
+    pub fn get_alerts(&self, now: DateTime<Local>) -> Vec<Alert> {
+        let mut alerts = self
+            .value_alerts
+            .iter()
+            .map(|((topic, index), since)| Alert {
+                topic: topic.clone(),
+                rule: self.alert_rules[*index].to_string(),
+                since: *since,
+            })
+            .collect::<Vec<_>>();
+        let silence_rules = self
+            .alert_rules
+            .iter()
+            .filter_map(|rule| match rule.condition {
+                AlertCondition::Silent(seconds) => Some((rule, seconds)),
+                _ => None,
+            });
+        for (rule, seconds) in silence_rules {
+            let max_silence = Duration::seconds(seconds.into());
+            for (topic, id) in &self.ids {
+                let last_received = self.tree.get(*id).unwrap().value().last_received;
+                if let Some(last_received) = last_received {
+                    let since = last_received + max_silence;
+                    if since < now && topic_filter_matches(&rule.filter, topic) {
+                        alerts.push(Alert {
+                            topic: topic.clone(),
+                            rule: rule.to_string(),
+                            since,
+                        });
                     }
                 }
-                Edge::Close(_) => {}
             }
         }
-        let mut results = HashSet::new();
-        for res in res_vector {
-            let mut full_topic = vec![imei.to_string()];
-            let mut temp_res = res.clone();
-            loop {
-                if let Some(parent) = temp_res.parent() {
-                    if *parent.value().leaf == *"" {
-                        break;
-                    }
-                    full_topic.insert(0, parent.value().leaf.to_string());
-                    temp_res = parent;
-                } else {
+        alerts.sort_by(|a, b| a.since.cmp(&b.since).then_with(|| a.topic.cmp(&b.topic)));
+        alerts
+    }
+
+    /// Drop the oldest entries of the topic over the limit of entries per topic
+    fn drop_over_topic_limit(&mut self, id: NodeId) {
+        let max = match self.limits.entries_per_topic {
+            Some(max) => max,
+            None => return,
+        };
+        let mut node = self.tree.get_mut(id).unwrap();
+        let topic = node.value();
+        while topic.history.len() > max {
+            let dropped = topic.drop_oldest().expect("more than max entries");
+            self.memory -= estimated_size(&dropped);
+            self.dropped_total += 1;
+            self.revision += 1;
+        }
+    }
+
+    /// Drop the entries which got older than the [`HistoryLimits`] while no new ones arrived
+    pub fn drop_expired(&mut self, now: DateTime<Local>) {
+        if self.limits.age.is_some() {
+            self.drop_over_limits(now);
+        }
+    }
+
+    /// Drop the oldest entries over all topics until they are within the [`HistoryLimits`]
+    fn drop_over_limits(&mut self, now: DateTime<Local>) {
+        while let Some(oldest) = self.arrivals.front() {
+            let mut node = self.tree.get_mut(oldest.id).unwrap();
+            let topic = node.value();
+            // Already dropped by the limit of entries per topic
+            let is_dropped = oldest.index < topic.dropped;
+            let too_old = self
+                .limits
+                .age
+                .map_or(false, |max_age| now - oldest.time > max_age);
+            // Arrivals of already dropped entries count too, otherwise they could pile up
+            let memory = self.memory + self.arrivals.len() * std::mem::size_of::<Arrival>();
+            let too_much_memory = self
+                .limits
+                .memory
+                .map_or(false, |max_memory| memory > max_memory);
+            if !is_dropped {
+                if !too_old && !too_much_memory {
                     break;
                 }
+                let dropped = topic.drop_oldest().expect("arrival is not yet dropped");
+                self.memory -= estimated_size(&dropped);
+                self.dropped_total += 1;
+                self.revision += 1;
+            }
+            self.arrivals.pop_front();
+        }
+    }
+
+    #[cfg(test)]
+    pub fn example() -> Self {
+        let mut history = Self::new();
+        history.add(
+            &Publish::new("test", rumqttc::QoS::AtLeastOnce, "A"),
+            Local::now(),
+        );
+        history.add(
+            &Publish::new("foo/test", rumqttc::QoS::AtLeastOnce, "B"),
+            Local::now(),
+        );
+        history.add(
+            &Publish::new("test", rumqttc::QoS::AtLeastOnce, "C"),
+            Local::now(),
+        );
+        history.add(
+            &Publish::new("foo/bar", rumqttc::QoS::AtLeastOnce, "D"),
+            Local::now(),
+        );
+        history
+    }
+}
+
+/// The history of a broker or the histories of multiple brokers merged into one tree.
+/// The merged tree has the brokers as top-level nodes.
+pub struct HistoryView<'a> {
+    /// Histories with the name of their broker when merged, ordered by it
+    sources: Vec<(Option<&'a str>, &'a MqttHistory)>,
+    /// Entries and topics received after this time are not shown, used to pause while the histories keep receiving.
+    /// Alerts are not affected.
+    until: Option<DateTime<Local>>,
+}
+
+impl<'a> HistoryView<'a> {
+    pub fn new(
+        mut sources: Vec<(Option<&'a str>, &'a MqttHistory)>,
+        until: Option<DateTime<Local>>,
+    ) -> Self {
+        sources.sort_by_key(|(broker, _)| *broker);
+        Self { sources, until }
+    }
+
+    /// Top-level nodes in alphabetical order
+    fn roots(&self) -> Vec<Node<'a>> {
+        self.sources
+            .iter()
+            .flat_map(|(broker, history)| {
+                let root = history.tree.root();
+                if broker.is_some() {
+                    vec![Node {
+                        tree_ref: root,
+                        broker: *broker,
+                        until: self.until,
+                    }]
+                } else {
+                    Node::topic(root, self.until).children().collect()
+                }
+            })
+            .collect()
+    }
+
+    fn node(&self, topic: &str) -> Option<Node<'a>> {
+        self.sources
+            .iter()
+            .find_map(|(broker, history)| match broker {
+                None => history
+                    .node(topic)
+                    .map(|node| Node::topic(node, self.until)),
+                Some(broker) => match topic.strip_prefix(broker)? {
+                    "" => Some(Node {
+                        tree_ref: history.tree.root(),
+                        broker: Some(broker),
+                        until: self.until,
+                    }),
+                    rest => history
+                        .node(rest.strip_prefix('/')?)
+                        .map(|node| Node::topic(node, self.until)),
+                },
+            })
+            .filter(|node| node.is_shown())
+    }
+
+    /// Amount of messages added to all histories including the dropped ones
+    pub fn get_message_total(&self) -> usize {
+        self.sources
+            .iter()
+            .map(|(_, history)| history.get_message_total())
+            .sum()
+    }
+
+    /// Amount of entries dropped from all histories because of the [`HistoryLimits`]
+    pub fn get_dropped_total(&self) -> usize {
+        self.sources
+            .iter()
+            .map(|(_, history)| history.get_dropped_total())
+            .sum()
+    }
+
+    pub fn get_store_error(&self) -> Option<&'a str> {
+        self.sources
+            .iter()
+            .find_map(|(_, history)| history.get_store_error())
+    }
+
+    /// Active alerts of all histories, oldest first. See [`MqttHistory::get_alerts`].
+    pub fn get_alerts(&self, now: DateTime<Local>) -> Vec<Alert> {
+        let mut alerts = self
+            .sources
+            .iter()
+            .flat_map(|(broker, history)| {
+                history.get_alerts(now).into_iter().map(move |mut alert| {
+                    if let Some(broker) = broker {
+                        alert.topic = format!("{broker}/{}", alert.topic);
+                    }
+                    alert
+                })
+            })
+            .collect::<Vec<_>>();
+        alerts.sort_by(|a, b| a.since.cmp(&b.since).then_with(|| a.topic.cmp(&b.topic)));
+        alerts
+    }
+
+    /// The topic without the broker level of the merged view. None for the node of a broker.
+    pub fn strip_broker<'t>(&self, topic: &'t str) -> Option<&'t str> {
+        if self.sources.iter().any(|(broker, _)| broker.is_some()) {
+            topic.split_once('/').map(|(_, rest)| rest)
+        } else {
+            Some(topic)
+        }
+    }
+
+    /// Topics with messages matching the query. The brokers of the merged view are not matched.
+    pub fn search(&self, query: &Query) -> HashSet<String> {
+        fn build_recursive(
+            query: &Query,
+            prefix: &[&str],
+            node: Node,
+            broker_levels: usize,
+            hits: &mut HashSet<String>,
+        ) {
+            let mut topic = prefix.to_vec();
+            topic.push(node.leaf());
+            let history = node.entries();
+            if !history.is_empty()
+                && query.matches(
+                    &topic[broker_levels..].join("/"),
+                    history.back().map(|o| &o.payload),
+                )
+            {
+                hits.insert(topic.join("/"));
+            }
+            for child in node.children() {
+                build_recursive(query, &topic, child, broker_levels, hits);
             }
-            results.insert(full_topic.join("/"));
         }
-        if results.is_empty() {
-            return None;
+
+        let mut hits = HashSet::new();
+        for root in self.roots() {
+            let broker_levels = usize::from(root.broker.is_some());
+            build_recursive(query, &[], root, broker_levels, &mut hits);
         }
-        Some(results)
+        hits
     }
 
-    pub fn add(&mut self, packet: &Publish, time: DateTime<Local>) {
-        let id = self.entry(&packet.topic);
-        self.tree
-            .get_mut(id)
-            .unwrap()
-            .value()
-            .history
-            .push(HistoryEntry::new(packet, time));
+    /// Amount of topics which received messages and are not hidden by [`Self::until`].
+    /// Topics are never removed from the tree so this grows with every new one.
+    pub fn get_topic_amount(&self) -> usize {
+        self.sources
+            .iter()
+            .map(|(_, history)| match self.until {
+                None => history.ids.len(),
+                Some(until) => history
+                    .ids
+                    .values()
+                    .filter(|id| history.tree.get(**id).unwrap().value().created <= until)
+                    .count(),
+            })
+            .sum()
     }
 
-    pub fn get(&self, topic: &str) -> Option<&Vec<HistoryEntry>> {
-        let id = self.ids.get(topic)?;
-        self.tree.get(*id).map(|node| &node.value().history)
+    /// Increases with every change of the entries, see [`MqttHistory::revision`]
+    pub fn get_revision(&self) -> usize {
+        self.sources
+            .iter()
+            .map(|(_, history)| history.revision)
+            .sum()
     }
 
-    pub fn get_last(&self, topic: &str) -> Option<&HistoryEntry> {
-        let id = self.ids.get(topic)?;
-        self.tree
-            .get(*id)
-            .and_then(|node| node.value().history.last())
+    /// Entries of the topic. None when there are none (anymore).
+    pub fn get(&self, topic: &str) -> Option<Entries<'a>> {
+        self.node(topic)
+            .map(Node::entries)
+            .filter(|history| !history.is_empty())
     }
 
-    pub fn get_tree_identifier(&self, topic: &str) -> Option<TreeIdentifierVec> {
+    /// Amount of messages ever received on the topic including the dropped ones
+    pub fn get_message_count(&self, topic: &str) -> usize {
+        self.node(topic)
+            .map_or(0, |node| node.value().dropped + node.entries().len())
+    }
+
+    /// Amount of entries of the topic dropped because of the [`HistoryLimits`]
+    pub fn get_dropped(&self, topic: &str) -> usize {
+        self.node(topic).map_or(0, |node| node.value().dropped)
+    }
+
+    /// Entries of the same topic on all brokers of the merged view ordered by time.
+    ///
+    /// The first level of `topic` is the broker.
+    pub fn get_across_brokers(&self, topic: &str) -> Vec<(&'a str, &'a HistoryEntry)> {
+        let rest = match topic.split_once('/') {
+            Some((_, rest)) => rest,
+            None => return Vec::new(),
+        };
+        let mut entries = self
+            .sources
+            .iter()
+            .filter_map(|(broker, history)| Some(((*broker)?, history.node(rest)?)))
+            .flat_map(|(broker, node)| {
+                Node::topic(node, self.until)
+                    .entries()
+                    .iter()
+                    .map(move |entry| (broker, entry))
+            })
+            .collect::<Vec<_>>();
+        // Stable sort keeps the order per broker for equal times. Retained ones come first.
+        entries.sort_by_key(|(_, entry)| entry.time.as_optional());
+        entries
+    }
+
+    /// Identifier of the topic in the [`TreeItem`]s of the given order
+    pub fn get_tree_identifier(&self, topic: &str, sort: SortOrder) -> Option<TreeIdentifierVec> {
         let mut identifier = Vec::new();
-        let mut parent = self.tree.root();
+        let mut parent = None;
         for part in topic.split('/') {
-            let (index, child) = parent
-                .children()
+            let children = parent.map_or_else(|| sort.sorted(self.roots()), |p| sort.children(p));
+            let (index, child) = children
+                .into_iter()
                 .enumerate()
-                .find(|(_i, o)| &*o.value().leaf == part)?;
+                .find(|(_i, o)| o.leaf() == part)?;
             identifier.push(index);
-            parent = child;
+            parent = Some(child);
         }
         Some(identifier)
     }
 
+    /// All topics with entries
+    pub fn get_topics(&self) -> Vec<String> {
+        self.roots()
+            .into_iter()
+            .flat_map(|root| self.get_topics_below(root.leaf()))
+            .collect()
+    }
+
     pub fn get_topics_below(&self, topic: &str) -> Vec<String> {
-        fn build_recursive(prefix: &[&str], node: NodeRef<Topic>) -> Vec<String> {
+        fn build_recursive(prefix: &[&str], node: Node) -> Vec<String> {
             let mut topic = prefix.to_vec();
-            topic.push(&node.value().leaf);
+            topic.push(node.leaf());
 
             let mut entries_below = node
                 .children()
                 .flat_map(|c| build_recursive(&topic, c))
                 .collect::<Vec<_>>();
-            if !node.value().history.is_empty() {
+            if !node.entries().is_empty() {
                 entries_below.insert(0, topic.join("/"));
             }
             entries_below
         }
 
         // Get the node of the given topic in the tree
-        let mut noderef = self.tree.root();
-        for part in topic.split('/') {
-            let node = noderef.children().find(|o| &*o.value().leaf == part);
-            if let Some(node) = node {
-                noderef = node;
-            } else {
-                // Node not found -> there are no topics below
-                return vec![];
-            }
-        }
+        let node = match self.node(topic) {
+            Some(node) => node,
+            // Node not found -> there are no topics below
+            None => return vec![],
+        };
 
         let mut prefix = topic.split('/').collect::<Vec<_>>();
         prefix.pop(); // The node itself will also be added so its not part of the prefix
-        build_recursive(&prefix, noderef)
+        build_recursive(&prefix, node)
+    }
+
+    /// Topics with other topics below them which can be opened in the overview.
+    /// Only the ones below the given topic including itself when one is given.
+    pub fn get_expandable_topics(&self, below: Option<&str>) -> Vec<String> {
+        fn build_recursive(prefix: &[&str], node: Node, topics: &mut Vec<String>) {
+            if node.children().next().is_none() {
+                return;
+            }
+            let mut topic = prefix.to_vec();
+            topic.push(node.leaf());
+            topics.push(topic.join("/"));
+            for child in node.children() {
+                build_recursive(&topic, child, topics);
+            }
+        }
+
+        let mut topics = Vec::new();
+        if let Some(below) = below {
+            if let Some(node) = self.node(below) {
+                let mut prefix = below.split('/').collect::<Vec<_>>();
+                prefix.pop(); // The node itself will also be added so its not part of the prefix
+                build_recursive(&prefix, node, &mut topics);
+            }
+        } else {
+            for root in self.roots() {
+                build_recursive(&[], root, &mut topics);
+            }
+        }
+        topics
     }
 
+    /// Topics shown in the overview. While searching only the hits with the topics above and below them are shown
+    /// and the topics above them are opened.
     pub fn get_visible_topics(
         &self,
         opened_topics: &HashSet<String>,
-        query_items: &Option<HashSet<String>>,
+        hits: Option<&TopicSet>,
+        sort: SortOrder,
     ) -> Vec<String> {
         fn build_recursive(
             opened_topics: &HashSet<String>,
             prefix: &[&str],
-            node: NodeRef<Topic>,
-            query_items: &Option<HashSet<String>>,
+            node: Node,
+            hits: Option<&TopicSet>,
+            sort: SortOrder,
         ) -> Vec<String> {
             let mut topic = prefix.to_vec();
-            topic.push(&node.value().leaf);
+            topic.push(node.leaf());
 
             let topic_string = topic.join("/");
+            if !hits.map_or(true, |hits| hits.shows(&topic_string)) {
+                return vec![];
+            }
 
-            if opened_topics.contains(&topic_string) {
-                let mut entries_below = node
-                    .children()
-                    .flat_map(|c| build_recursive(opened_topics, &topic, c, query_items))
+            let is_opened = opened_topics.contains(&topic_string)
+                || hits.map_or(false, |hits| hits.is_above(&topic_string));
+            if is_opened {
+                let mut entries_below = sort
+                    .children(node)
+                    .into_iter()
+                    .flat_map(|c| build_recursive(opened_topics, &topic, c, hits, sort))
                     .collect::<Vec<_>>();
                 entries_below.insert(0, topic_string);
                 entries_below
@@ -212,45 +958,31 @@ impl MqttHistory {
             }
         }
 
-        let res = self
-            .tree
-            .root()
-            .children()
-            .flat_map(|o| build_recursive(opened_topics, &[], o, query_items));
-        if let Some(hash) = query_items.as_ref() {
-            return res
-                .filter(|i| {
-                    hash.into_iter()
-                        .map(|j| j.contains(i) || i.starts_with(j))
-                        .reduce(|acc, x| acc || x)
-                        .unwrap_or(false)
-                })
-                .collect::<Vec<_>>();
-        }
-        res.collect::<Vec<_>>()
+        sort.sorted(self.roots())
+            .into_iter()
+            .flat_map(|o| build_recursive(opened_topics, &[], o, hits, sort))
+            .collect()
     }
 
     /// Returns (`topic_amount`, `TreeItem`s)
-    pub fn to_tree_items(&self, query_items: &Option<HashSet<String>>) -> (usize, Vec<TreeItem>) {
+    pub fn to_tree_items(&self, view: &TreeItemsView) -> (usize, Vec<TreeItem<'a>>) {
         fn build_recursive<'a>(
             prefix: &[&str],
-            node: NodeRef<'a, Topic>,
-            query_items: &Option<HashSet<String>>,
+            node: Node<'a>,
+            view: &TreeItemsView,
         ) -> RecursiveTreeItemGenerator<'a> {
-            let Topic { leaf, history } = node.value();
+            let leaf = node.leaf();
+            let history = node.entries();
+            let dropped = node.value().dropped;
             let mut topic = prefix.to_vec();
             topic.push(leaf);
-            let mut must_show = true;
-            if let Some(hash) = query_items.as_ref() {
-                must_show = hash
-                    .into_iter()
-                    .map(|i| i.contains(&topic.join("/")) || topic.join("/").starts_with(i))
-                    .reduce(|acc, x| acc || x)
-                    .unwrap_or(false);
-            }
-            let entries_below = node
-                .children()
-                .map(|c| build_recursive(&topic, c, query_items))
+            let joined = topic.join("/");
+            let must_show = view.hits.map_or(true, |hits| hits.shows(&joined));
+            let entries_below = view
+                .sort
+                .children(node)
+                .into_iter()
+                .map(|c| build_recursive(&topic, c, view))
                 .collect::<Vec<_>>();
             let messages_below = entries_below
                 .iter()
@@ -263,36 +995,61 @@ impl MqttHistory {
                     has_messages + below.topics_below
                 })
                 .sum();
+            let own_received = history.back().map(|entry| entry.received);
+            let last_received = entries_below
+                .iter()
+                .filter_map(|below| below.last_received)
+                .chain(own_received)
+                .max();
             let children = entries_below
                 .into_iter()
                 .map(|o| o.tree_item)
                 .collect::<Vec<_>>();
 
-            let meta = match history.last().map(|o| &o.payload) {
+            let meta = match history.back().map(|o| &o.payload) {
                 Some(Payload::String(str)) => format!("= {str}"),
                 Some(Payload::Json(json)) => format!("= {}", json.dump()),
                 Some(Payload::NotUtf8(_)) => "Payload not UTF-8".to_string(),
                 None => format!("({topics_below} topics, {messages_below} messages)"),
             };
-            let text = vec![Spans::from(vec![
-                Span::styled(leaf.as_ref(), STYLE_BOLD),
-                Span::raw(" "),
-                Span::styled(meta, STYLE_DARKGRAY),
-            ])];
+            // Updates below a collapsed topic are shown on the topic itself
+            let is_collapsed = !view.opened.contains(&joined)
+                && !view.hits.map_or(false, |hits| hits.is_above(&joined));
+            let updated = if is_collapsed {
+                last_received
+            } else {
+                own_received
+            };
+            let leaf_style = if view.alerting.contains(&joined) || view.alerting.is_above(&joined) {
+                STYLE_ALERT
+            } else {
+                updated
+                    .and_then(|updated| recently_updated_style(updated, view.now))
+                    .unwrap_or(STYLE_BOLD)
+            };
+            let mut spans = vec![Span::styled(leaf, leaf_style), Span::raw(" ")];
+            let unseen = (dropped + history.len())
+                .saturating_sub(view.seen.get(&joined).copied().unwrap_or(0));
+            if unseen > 0 && view.selected != Some(joined.as_str()) {
+                spans.push(Span::styled(format!("+{unseen}"), STYLE_UNSEEN));
+                spans.push(Span::raw(" "));
+            }
+            spans.push(Span::styled(meta, STYLE_DARKGRAY));
 
             RecursiveTreeItemGenerator {
                 messages_below,
                 messages: history.len(),
                 topics_below,
-                tree_item: TreeItem::new(text, children).visible(must_show),
+                last_received,
+                tree_item: TreeItem::new(vec![Spans::from(spans)], children).visible(must_show),
             }
         }
 
-        let children = self
-            .tree
-            .root()
-            .children()
-            .map(|o| build_recursive(&[], o, query_items))
+        let children = view
+            .sort
+            .sorted(self.roots())
+            .into_iter()
+            .map(|o| build_recursive(&[], o, view))
             .collect::<Vec<_>>();
 
         let topics = children
@@ -305,55 +1062,60 @@ impl MqttHistory {
             .collect::<Vec<_>>();
         (topics, items)
     }
-
-    #[cfg(test)]
-    pub fn example() -> Self {
-        let mut history = Self::new();
-        history.add(
-            &Publish::new("test", rumqttc::QoS::AtLeastOnce, "A"),
-            Local::now(),
-        );
-        history.add(
-            &Publish::new("foo/test", rumqttc::QoS::AtLeastOnce, "B"),
-            Local::now(),
-        );
-        history.add(
-            &Publish::new("test", rumqttc::QoS::AtLeastOnce, "C"),
-            Local::now(),
-        );
-        history.add(
-            &Publish::new("foo/bar", rumqttc::QoS::AtLeastOnce, "D"),
-            Local::now(),
-        );
-        history
-    }
 }
 
 #[test]
 fn tree_identifier_works() {
     let history = MqttHistory::example();
-    assert_eq!(history.get_tree_identifier("whatever"), None);
-    assert_eq!(history.get_tree_identifier("test").unwrap(), [1]);
-    assert_eq!(history.get_tree_identifier("foo/bar").unwrap(), [0, 0]);
-    assert_eq!(history.get_tree_identifier("foo/test").unwrap(), [0, 1]);
+    assert_eq!(
+        history
+            .view()
+            .get_tree_identifier("whatever", SortOrder::default()),
+        None
+    );
+    assert_eq!(
+        history
+            .view()
+            .get_tree_identifier("test", SortOrder::default())
+            .unwrap(),
+        [1]
+    );
+    assert_eq!(
+        history
+            .view()
+            .get_tree_identifier("foo/bar", SortOrder::default())
+            .unwrap(),
+        [0, 0]
+    );
+    assert_eq!(
+        history
+            .view()
+            .get_tree_identifier("foo/test", SortOrder::default())
+            .unwrap(),
+        [0, 1]
+    );
 }
 
 #[test]
 fn topics_below_works() {
-    let actual = MqttHistory::example().get_topics_below("foo");
+    let actual = MqttHistory::example().view().get_topics_below("foo");
     assert_eq!(actual, ["foo/bar", "foo/test"]);
 }
 
 #[test]
 fn topics_below_finds_itself_works() {
-    let actual = MqttHistory::example().get_topics_below("test");
+    let actual = MqttHistory::example().view().get_topics_below("test");
     assert_eq!(actual, ["test"]);
 }
 
 #[test]
 fn visible_all_closed_works() {
     let opened_topics = HashSet::new();
-    let actual = MqttHistory::example().get_visible_topics(&opened_topics, &Some(HashSet::new()));
+    let actual = MqttHistory::example().view().get_visible_topics(
+        &opened_topics,
+        None,
+        SortOrder::default(),
+    );
     assert_eq!(actual, ["foo", "test"]);
 }
 
@@ -361,14 +1123,311 @@ fn visible_all_closed_works() {
 fn visible_opened_works() {
     let mut opened_topics = HashSet::new();
     opened_topics.insert("foo".into());
-    let actual = MqttHistory::example().get_visible_topics(&opened_topics, &Some(HashSet::new()));
+    let actual = MqttHistory::example().view().get_visible_topics(
+        &opened_topics,
+        None,
+        SortOrder::default(),
+    );
     assert_eq!(actual, ["foo", "foo/bar", "foo/test", "test"]);
 }
 
+#[test]
+fn search_returns_full_topics() {
+    let mut history = MqttHistory::example();
+    history.add(
+        &Publish::new("bar/test", rumqttc::QoS::AtLeastOnce, "E"),
+        Local::now(),
+    );
+    let query = Query::parse("test", false, crate::interactive::search::Target::Topic)
+        .unwrap()
+        .unwrap();
+    let mut hits = history
+        .view()
+        .search(&query)
+        .into_iter()
+        .collect::<Vec<_>>();
+    hits.sort();
+    assert_eq!(hits, ["bar/test", "foo/test", "test"]);
+
+    let query = Query::parse("D", false, crate::interactive::search::Target::Payload)
+        .unwrap()
+        .unwrap();
+    let hits = history.view().search(&query);
+    assert_eq!(hits.into_iter().collect::<Vec<_>>(), ["foo/bar"]);
+}
+
+#[test]
+fn visible_while_searching_opens_topics_above_hits() {
+    let hits = TopicSet::new(HashSet::from(["foo/bar".to_string()]));
+    let actual = MqttHistory::example().view().get_visible_topics(
+        &HashSet::new(),
+        Some(&hits),
+        SortOrder::default(),
+    );
+    assert_eq!(actual, ["foo", "foo/bar"]);
+
+    let actual = MqttHistory::example().view().get_visible_topics(
+        &HashSet::new(),
+        Some(&TopicSet::default()),
+        SortOrder::default(),
+    );
+    assert!(actual.is_empty());
+}
+
+#[test]
+fn expandable_topics_works() {
+    let mut history = MqttHistory::example();
+    history.add(
+        &Publish::new("foo/bar/baz", rumqttc::QoS::AtLeastOnce, "E"),
+        Local::now(),
+    );
+    assert_eq!(
+        history.view().get_expandable_topics(None),
+        ["foo", "foo/bar"]
+    );
+    assert_eq!(
+        history.view().get_expandable_topics(Some("foo/bar")),
+        ["foo/bar"]
+    );
+    assert!(history
+        .view()
+        .get_expandable_topics(Some("test"))
+        .is_empty());
+}
+
+#[test]
+fn brokers_are_top_level_nodes() {
+    let mut remote = MqttHistory::new();
+    remote.add(
+        &Publish::new("foo/bar", rumqttc::QoS::AtLeastOnce, "A"),
+        Local::now(),
+    );
+    let mut local = MqttHistory::new();
+    local.add(
+        &Publish::new("foo/bar", rumqttc::QoS::AtLeastOnce, "B"),
+        Local::now(),
+    );
+    let history = HistoryView::new(
+        vec![
+            (Some("remote:1883"), &remote),
+            (Some("localhost:1883"), &local),
+        ],
+        None,
+    );
+    let (topics, items) = history.to_tree_items(&TreeItemsView {
+        hits: None,
+        alerting: &TopicSet::default(),
+        sort: SortOrder::default(),
+        opened: &HashSet::new(),
+        selected: None,
+        seen: &HashMap::new(),
+        now: Local::now(),
+    });
+    assert_eq!(topics, 2);
+    assert_eq!(items.len(), 2);
+    assert_eq!(
+        history
+            .get_tree_identifier("remote:1883/foo/bar", SortOrder::default())
+            .unwrap(),
+        [1, 0, 0]
+    );
+    assert!(history.get("foo/bar").is_none());
+    assert!(history.get("remote:1883/foo/bar").is_some());
+    assert_eq!(
+        history.get_topics_below("localhost:1883"),
+        ["localhost:1883/foo/bar"]
+    );
+}
+
+#[test]
+fn search_in_merged_view_ignores_brokers() {
+    let mut remote = MqttHistory::new();
+    remote.add(
+        &Publish::new("foo/bar", rumqttc::QoS::AtLeastOnce, "A"),
+        Local::now(),
+    );
+    let history = HistoryView::new(vec![(Some("remote:1883"), &remote)], None);
+    let search = |input| {
+        let query = Query::parse(input, false, crate::interactive::search::Target::Topic)
+            .unwrap()
+            .unwrap();
+        history.search(&query).into_iter().collect::<Vec<_>>()
+    };
+    assert_eq!(search("foo/+"), ["remote:1883/foo/bar"]);
+    assert!(search("remote").is_empty());
+}
+
+#[test]
+fn across_brokers_is_ordered_by_time() {
+    let now = Local::now();
+    let add = |history: &mut MqttHistory, topic: &str, payload: &str, time| {
+        history.add(
+            &Publish::new(topic, rumqttc::QoS::AtLeastOnce, payload),
+            time,
+        );
+    };
+    let mut a = MqttHistory::new();
+    let mut b = MqttHistory::new();
+    add(&mut a, "foo", "1", now);
+    add(&mut b, "foo", "0", now - chrono::Duration::seconds(1));
+    add(&mut a, "foo", "2", now + chrono::Duration::seconds(1));
+    add(&mut b, "other", "X", now);
+    let history = HistoryView::new(vec![(Some("a"), &a), (Some("b"), &b)], None);
+
+    let actual = history
+        .get_across_brokers("b/foo")
+        .into_iter()
+        .map(|(broker, entry)| match &entry.payload {
+            Payload::Json(json) => format!("{broker} {}", json.dump()),
+            _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(actual, ["b 0", "a 1", "a 2"]);
+    assert!(history.get_across_brokers("b").is_empty());
+}
+
+#[test]
+fn max_entries_per_topic_drops_oldest() {
+    let mut history = MqttHistory::with_limits(HistoryLimits {
+        entries_per_topic: Some(2),
+        ..HistoryLimits::default()
+    });
+    for payload in ["1", "2", "3"] {
+        history.add(
+            &Publish::new("foo", rumqttc::QoS::AtLeastOnce, payload),
+            Local::now(),
+        );
+    }
+    let remaining = history
+        .view()
+        .get("foo")
+        .unwrap()
+        .iter()
+        .map(|entry| entry.payload.as_optional_json().unwrap().dump())
+        .collect::<Vec<_>>();
+    assert_eq!(remaining, ["2", "3"]);
+    assert_eq!(history.view().get_dropped("foo"), 1);
+    assert_eq!(history.view().get_dropped("bar"), 0);
+}
+
+#[test]
+fn max_age_drops_old_entries_of_all_topics() {
+    let start = Local::now();
+    let mut history = MqttHistory::with_limits(HistoryLimits {
+        age: Some(Duration::seconds(60)),
+        ..HistoryLimits::default()
+    });
+    history.add(&Publish::new("foo", rumqttc::QoS::AtLeastOnce, "1"), start);
+    history.add(
+        &Publish::new("bar", rumqttc::QoS::AtLeastOnce, "2"),
+        start + Duration::seconds(30),
+    );
+    history.add(
+        &Publish::new("bar", rumqttc::QoS::AtLeastOnce, "3"),
+        start + Duration::seconds(61),
+    );
+    assert!(history.view().get("foo").is_none());
+    assert_eq!(history.view().get_dropped("foo"), 1);
+    assert_eq!(history.view().get("bar").unwrap().len(), 2);
+
+    // Without new messages
+    history.drop_expired(start + Duration::seconds(91));
+    assert_eq!(history.view().get("bar").unwrap().len(), 1);
+    history.drop_expired(start + Duration::seconds(122));
+    assert!(history.view().get("bar").is_none());
+    // The topic itself stays in the tree
+    assert!(history
+        .view()
+        .get_tree_identifier("foo", SortOrder::default())
+        .is_some());
+}
+
+#[test]
+fn records_are_the_kept_entries_in_order() {
+    let start = Local::now();
+    let mut history = MqttHistory::with_limits(HistoryLimits {
+        entries_per_topic: Some(1),
+        ..HistoryLimits::default()
+    });
+    let mut retained = Publish::new("foo", rumqttc::QoS::AtLeastOnce, "1");
+    retained.retain = true;
+    history.add(&retained, start);
+    history.add(
+        &Publish::new("bar", rumqttc::QoS::AtMostOnce, "2"),
+        start + Duration::seconds(1),
+    );
+    history.add(
+        &Publish::new("bar", rumqttc::QoS::AtMostOnce, "3"),
+        start + Duration::seconds(2),
+    );
+    let records = history.records();
+    assert_eq!(
+        records,
+        [
+            (retained, start),
+            (
+                Publish::new("bar", rumqttc::QoS::AtMostOnce, "3"),
+                start + Duration::seconds(2)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn max_memory_drops_oldest_entries() {
+    let entry_size = std::mem::size_of::<HistoryEntry>() + 2;
+    let arrival_size = std::mem::size_of::<Arrival>();
+    let mut history = MqttHistory::with_limits(HistoryLimits {
+        memory: Some((entry_size + arrival_size) * 2),
+        entries_per_topic: Some(2),
+        ..HistoryLimits::default()
+    });
+    let now = Local::now();
+    for (topic, payload) in [("foo", "1"), ("foo", "2"), ("foo", "3"), ("bar", "4")] {
+        history.add(
+            &Publish::new(topic, rumqttc::QoS::AtLeastOnce, payload),
+            now,
+        );
+    }
+    assert_eq!(history.memory, entry_size * 2);
+    assert_eq!(history.view().get("foo").unwrap().len(), 1);
+    assert_eq!(history.view().get_dropped("foo"), 2);
+    assert_eq!(history.view().get("bar").unwrap().len(), 1);
+    assert_eq!(history.arrivals.len(), 2);
+}
+
+#[test]
+fn estimated_size_counts_payload_not_utf8_once() {
+    let text = HistoryEntry::new(
+        &Publish::new("foo", rumqttc::QoS::AtLeastOnce, "42"),
+        Local::now(),
+    );
+    assert_eq!(
+        estimated_size(&text),
+        std::mem::size_of::<HistoryEntry>() + 4
+    );
+    let binary = HistoryEntry::new(
+        &Publish::new("foo", rumqttc::QoS::AtLeastOnce, vec![0xff, 0x00]),
+        Local::now(),
+    );
+    assert_eq!(
+        estimated_size(&binary),
+        std::mem::size_of::<HistoryEntry>() + 2
+    );
+}
+
 #[test]
 fn tree_items_works() {
     let example = MqttHistory::example();
-    let (topics, items) = example.to_tree_items(&Some(HashSet::new()));
+    let (topics, items) = example.view().to_tree_items(&TreeItemsView {
+        hits: None,
+        alerting: &TopicSet::default(),
+        sort: SortOrder::default(),
+        opened: &HashSet::new(),
+        selected: None,
+        seen: &HashMap::new(),
+        now: Local::now(),
+    });
     assert_eq!(topics, 3);
     dbg!(&items);
     assert_eq!(items.len(), 2);
@@ -377,3 +1436,204 @@ fn tree_items_works() {
     assert!(items[0].child(2).is_none());
     assert!(items[1].child(0).is_none());
 }
+
+#[test]
+fn alerts_follow_values_and_silence() {
+    let mut history = MqttHistory::new();
+    history.set_alert_rules(vec![
+        "home/+/temperature > 30".parse().unwrap(),
+        "home/# silent 60".parse().unwrap(),
+    ]);
+    let now = Local::now();
+    let add = |history: &mut MqttHistory, topic: &str, payload: &str, time| {
+        history.add(
+            &Publish::new(topic, rumqttc::QoS::AtLeastOnce, payload),
+            time,
+        );
+    };
+    add(&mut history, "home/kitchen/temperature", "31", now);
+    add(&mut history, "home/bath/temperature", "20", now);
+    let alerts = history.get_alerts(now);
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].topic, "home/kitchen/temperature");
+    assert_eq!(alerts[0].rule, "home/+/temperature > 30");
+
+    add(&mut history, "home/kitchen/temperature", "25", now);
+    assert!(history.get_alerts(now).is_empty());
+
+    let later = now + Duration::seconds(61);
+    let silent = history
+        .get_alerts(later)
+        .into_iter()
+        .map(|alert| alert.topic)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        silent,
+        ["home/bath/temperature", "home/kitchen/temperature"]
+    );
+}
+
+#[test]
+fn silence_alerts_outlive_dropped_entries() {
+    let mut history = MqttHistory::with_limits(HistoryLimits {
+        age: Some(Duration::seconds(30)),
+        ..HistoryLimits::default()
+    });
+    history.set_alert_rules(vec!["foo silent 60".parse().unwrap()]);
+    let now = Local::now();
+    history.add(&Publish::new("foo", rumqttc::QoS::AtLeastOnce, "1"), now);
+    let later = now + Duration::seconds(61);
+    history.drop_expired(later);
+    assert!(history.view().get("foo").is_none());
+    let alerts = history.get_alerts(later);
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].topic, "foo");
+}
+
+#[test]
+fn sort_orders_rank_by_topics_below() {
+    let mut history = MqttHistory::new();
+    let now = Local::now();
+    for (topic, payload, seconds) in [
+        ("a/one", "1", 0),
+        ("a/two", "22", 10),
+        ("b", "333", 5),
+        ("b", "4444", 6),
+        ("b", "5", 7),
+    ] {
+        history.add(
+            &Publish::new(topic, rumqttc::QoS::AtLeastOnce, payload),
+            now + Duration::seconds(seconds),
+        );
+    }
+    let opened = HashSet::from(["a".to_string()]);
+    let visible = |sort| history.view().get_visible_topics(&opened, None, sort);
+    assert_eq!(
+        visible(SortOrder::Alphabetical),
+        ["a", "a/one", "a/two", "b"]
+    );
+    assert_eq!(
+        visible(SortOrder::RecentlyUpdated),
+        ["a", "a/two", "a/one", "b"]
+    );
+    assert_eq!(
+        visible(SortOrder::MostMessages),
+        ["b", "a", "a/one", "a/two"]
+    );
+    assert_eq!(
+        visible(SortOrder::MessageRate),
+        ["b", "a", "a/one", "a/two"]
+    );
+    assert_eq!(
+        visible(SortOrder::PayloadSize),
+        ["a", "a/two", "a/one", "b"]
+    );
+
+    assert_eq!(
+        history
+            .view()
+            .get_tree_identifier("a/two", SortOrder::Alphabetical),
+        Some(vec![0, 1])
+    );
+    assert_eq!(
+        history
+            .view()
+            .get_tree_identifier("a/two", SortOrder::MostMessages),
+        Some(vec![1, 1])
+    );
+    assert_eq!(
+        history
+            .view()
+            .get_tree_identifier("a/two", SortOrder::RecentlyUpdated),
+        Some(vec![0, 0])
+    );
+}
+
+#[test]
+fn recently_updated_fades() {
+    let now = Local::now();
+    let style =
+        |milliseconds| recently_updated_style(now - Duration::milliseconds(milliseconds), now);
+    assert_eq!(style(0), Some(STYLE_UPDATED.add_modifier(Modifier::BOLD)));
+    assert_eq!(style(2000), Some(STYLE_UPDATED));
+    assert_eq!(style(5000).and_then(|style| style.fg), Some(Color::Yellow));
+    assert_eq!(style(6000), None);
+}
+
+#[test]
+fn message_count_includes_dropped() {
+    let mut history = MqttHistory::with_limits(HistoryLimits {
+        entries_per_topic: Some(1),
+        ..HistoryLimits::default()
+    });
+    for payload in ["1", "2", "3"] {
+        history.add(
+            &Publish::new("foo", rumqttc::QoS::AtLeastOnce, payload),
+            Local::now(),
+        );
+    }
+    assert_eq!(history.view().get_message_count("foo"), 3);
+    assert_eq!(history.view().get_message_count("bar"), 0);
+}
+
+#[test]
+fn view_until_hides_later_entries_and_topics() {
+    let mut history = MqttHistory::example();
+    let paused = Local::now();
+    let later = paused + Duration::seconds(1);
+    history.add(&Publish::new("test", rumqttc::QoS::AtLeastOnce, "E"), later);
+    history.add(
+        &Publish::new("foo/new", rumqttc::QoS::AtLeastOnce, "1"),
+        later,
+    );
+    let view = HistoryView::new(vec![(None, &history)], Some(paused));
+    assert_eq!(view.get("test").unwrap().len(), 2);
+    assert_eq!(view.get_message_count("test"), 2);
+    assert!(view.get("foo/new").is_none());
+    assert_eq!(view.get_topics_below("foo"), ["foo/bar", "foo/test"]);
+    assert_eq!(
+        view.get_visible_topics(
+            &HashSet::from(["foo".to_string()]),
+            None,
+            SortOrder::default()
+        ),
+        ["foo", "foo/bar", "foo/test", "test"]
+    );
+    assert_eq!(history.view().get("test").unwrap().len(), 3);
+    assert!(history.view().get("foo/new").is_some());
+}
+
+#[test]
+fn limits_are_enforced_while_paused() {
+    let start = Local::now();
+    let mut history = MqttHistory::with_limits(HistoryLimits {
+        entries_per_topic: Some(2),
+        age: Some(Duration::seconds(60)),
+        ..HistoryLimits::default()
+    });
+    let add = |history: &mut MqttHistory, topic: &str, seconds| {
+        let publish = Publish::new(topic, rumqttc::QoS::AtLeastOnce, "1");
+        history.add(&publish, start + Duration::seconds(seconds));
+    };
+    add(&mut history, "foo", 0);
+    add(&mut history, "foo", 1);
+    add(&mut history, "bar", 2);
+    let paused = start + Duration::seconds(5);
+    for seconds in 10..15 {
+        add(&mut history, "foo", seconds);
+    }
+    assert_eq!(history.get_dropped_total(), 5);
+
+    // The paused view loses the entries dropped meanwhile and reports them
+    let view = HistoryView::new(vec![(None, &history)], Some(paused));
+    assert!(view.get("foo").is_none());
+    assert_eq!(view.get_dropped("foo"), 5);
+    assert_eq!(view.get("bar").unwrap().len(), 1);
+
+    history.drop_expired(start + Duration::seconds(70));
+    let view = HistoryView::new(vec![(None, &history)], Some(paused));
+    assert!(view.get("bar").is_none());
+    assert_eq!(view.get_dropped_total(), 6);
+    assert_eq!(history.view().get("foo").unwrap().len(), 2);
+    assert_eq!(history.view().get_message_count("foo"), 7);
+}
@@ -1,80 +1,139 @@
-use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::fs;
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::thread::{self, sleep};
 use std::time::Duration;
 
-use chrono::Local;
+use anyhow::Context;
+use chrono::{DateTime, Local};
 use rumqttc::{Client, Connection, ConnectionError, QoS};
 
-use crate::interactive::mqtt_history::MqttHistory;
+use crate::cli::AlertRule;
+use crate::interactive::mqtt_history::{HistoryLimits, HistoryView, MqttHistory};
+use crate::mqtt::capture;
+use crate::mqtt::subscription::SubscriptionTracker;
 
 type ConnectionErrorArc = Arc<RwLock<Option<ConnectionError>>>;
-type HistoryArc = Arc<RwLock<MqttHistory>>;
+pub type HistoryArc = Arc<RwLock<MqttHistory>>;
+type SubscriptionsArc = Arc<Mutex<SubscriptionTracker>>;
 
 pub struct MqttThread {
     client: Client,
+    /// Outcome of the first connection attempt, see [`Self::wait_connected`]
+    connected: mpsc::Receiver<Result<(), ConnectionError>>,
     connection_err: ConnectionErrorArc,
     history: HistoryArc,
+    subscriptions: SubscriptionsArc,
 }
 
 impl MqttThread {
     pub fn new(
-        mut client: Client,
-        mut connection: Connection,
+        client: Client,
+        connection: Connection,
         subscribe_topic: Vec<String>,
+        history_limits: HistoryLimits,
+        history_file: Option<&Path>,
+        alert_rules: &[AlertRule],
     ) -> anyhow::Result<Self> {
-        // Iterate until there is a ConnAck. When this fails it still fails in the main thread which is less messy. Happens for example when the host is wrong.
-        for notification in connection.iter() {
-            if let rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) = notification? {
-                break;
-            }
+        let mut history = MqttHistory::with_limits(history_limits);
+        history.set_alert_rules(alert_rules.to_vec());
+        if let Some(path) = history_file {
+            load_history(path, &mut history)
+                .with_context(|| format!("failed to load the history from {}", path.display()))?;
         }
 
-        for t in &subscribe_topic {
-            client.subscribe(t, QoS::ExactlyOnce)?;
-        }
+        // Subscribing happens on each ConnAck, the first one included
+        let subscriptions = SubscriptionTracker::new(subscribe_topic, QoS::ExactlyOnce);
 
         let connection_err = Arc::new(RwLock::new(None));
-        let history = Arc::new(RwLock::new(MqttHistory::new()));
+        let history = Arc::new(RwLock::new(history));
+        let subscriptions = Arc::new(Mutex::new(subscriptions));
+        let (connected_sender, connected) = mpsc::channel();
 
         {
             let client = client.clone();
             let connection_err = Arc::clone(&connection_err);
             let history = Arc::clone(&history);
+            let subscriptions = Arc::clone(&subscriptions);
             thread::Builder::new()
                 .name("mqtt connection".into())
                 .spawn(move || {
                     thread_logic(
                         client,
                         connection,
-                        &subscribe_topic,
+                        connected_sender,
                         &connection_err,
                         &history,
+                        &subscriptions,
                     );
                 })?;
         }
 
         Ok(Self {
             client,
+            connected,
             connection_err,
             history,
+            subscriptions,
         })
     }
 
+    /// Wait for the first `ConnAck`. Multiple brokers connect in parallel this way.
+    /// When this fails it still fails in the main thread which is less messy. Happens for example when the host is wrong.
+    pub fn wait_connected(&self) -> anyhow::Result<()> {
+        self.connected
+            .recv()
+            .map_err(|err| anyhow::anyhow!("mqtt connection thread stopped: {err}"))??;
+        Ok(())
+    }
+
     pub fn has_connection_err(&self) -> anyhow::Result<Option<String>> {
         match self.connection_err.read() {
-            Ok(err) => Ok(err.as_ref().map(std::string::ToString::to_string)),
+            Ok(err) => Ok(err.as_ref().map(|err| format_error(err))),
             Err(err) => Err(anyhow::anyhow!("mqtt history thread paniced {err}")),
         }
     }
 
-    pub fn get_history(&self) -> anyhow::Result<RwLockReadGuard<MqttHistory>> {
-        self.history
-            .read()
-            .map_err(|err| anyhow::anyhow!("failed to aquire lock of mqtt history: {err}"))
+    pub fn get_history(&self) -> anyhow::Result<RwLockReadGuard<'_, MqttHistory>> {
+        read_history(&self.history)
+    }
+
+    pub fn get_history_arc(&self) -> HistoryArc {
+        Arc::clone(&self.history)
+    }
+
+    /// Drop the entries which got too old even when no new messages arrive
+    pub fn drop_expired(&self) -> anyhow::Result<()> {
+        write_history(&self.history)?.drop_expired(Local::now());
+        Ok(())
+    }
+
+    /// Write the packets received since the last call to the history file
+    pub fn flush_history(&self) -> anyhow::Result<()> {
+        write_history(&self.history)?.flush_store();
+        Ok(())
+    }
+
+    pub fn get_subscriptions(&self) -> anyhow::Result<MutexGuard<'_, SubscriptionTracker>> {
+        lock_subscriptions(&self.subscriptions)
+    }
+
+    pub fn subscribe(&mut self, filter: String, qos: QoS) -> anyhow::Result<()> {
+        let mut subscriptions = lock_subscriptions(&self.subscriptions)?;
+        // Failing to send is shown as the state of the subscription
+        _ = subscriptions.subscribe(&mut self.client, filter, qos);
+        Ok(())
+    }
+
+    pub fn unsubscribe(&mut self, filter: &str) -> anyhow::Result<()> {
+        let mut subscriptions = lock_subscriptions(&self.subscriptions)?;
+        // Failing to send keeps the subscription in the list so it can be retried
+        _ = subscriptions.unsubscribe(&mut self.client, filter);
+        Ok(())
     }
 
     pub fn clean_below(&mut self, topic: &str) -> anyhow::Result<()> {
-        let topics = self.get_history()?.get_topics_below(topic);
+        let topics = self.get_history()?.view().get_topics_below(topic);
         for topic in topics {
             self.client.publish(topic, QoS::ExactlyOnce, true, [])?;
         }
@@ -82,29 +141,130 @@ impl MqttThread {
     }
 }
 
+pub fn read_history(history: &HistoryArc) -> anyhow::Result<RwLockReadGuard<'_, MqttHistory>> {
+    history
+        .read()
+        .map_err(|err| anyhow::anyhow!("failed to aquire lock of mqtt history: {err}"))
+}
+
+pub fn write_history(history: &HistoryArc) -> anyhow::Result<RwLockWriteGuard<'_, MqttHistory>> {
+    history
+        .write()
+        .map_err(|err| anyhow::anyhow!("failed to aquire lock of mqtt history: {err}"))
+}
+
+/// Locked histories of the brokers shown together, see [`HistoryView`]
+pub struct HistoryGuards<'a> {
+    guards: Vec<(Option<&'a str>, RwLockReadGuard<'a, MqttHistory>)>,
+    until: Option<DateTime<Local>>,
+}
+
+impl HistoryGuards<'_> {
+    pub fn view(&self) -> HistoryView<'_> {
+        HistoryView::new(
+            self.guards
+                .iter()
+                .map(|(broker, guard)| (*broker, &**guard))
+                .collect(),
+            self.until,
+        )
+    }
+}
+
+/// Read the histories, the ones with the name of their broker are merged into one tree.
+/// Their view does not show what was received after `until`, see [`HistoryView`].
+pub fn read_histories(
+    histories: &[(Option<String>, HistoryArc)],
+    until: Option<DateTime<Local>>,
+) -> anyhow::Result<HistoryGuards<'_>> {
+    let guards = histories
+        .iter()
+        .map(|(broker, history)| Ok((broker.as_deref(), read_history(history)?)))
+        .collect::<anyhow::Result<_>>()?;
+    Ok(HistoryGuards { guards, until })
+}
+
+/// Add the persisted history and continue to persist into the same file
+fn load_history(path: &Path, history: &mut MqttHistory) -> anyhow::Result<()> {
+    // Shorter files were cut short while writing the header
+    let is_new = fs::metadata(path).map_or(true, |metadata| {
+        metadata.len() < capture::MAGIC.len() as u64
+    });
+    if !is_new {
+        for record in capture::Reader::open(path)? {
+            let (packet, time) = record?;
+            history.add(&packet, time);
+        }
+    }
+    // Only keep what is within the limits. This also removes a record cut short at the end which would break appending.
+    capture::rewrite(path, &history.records())?;
+    history.persist_to(capture::Writer::append(path)?);
+    Ok(())
+}
+
+fn lock_subscriptions(
+    subscriptions: &SubscriptionsArc,
+) -> anyhow::Result<MutexGuard<'_, SubscriptionTracker>> {
+    subscriptions
+        .lock()
+        .map_err(|err| anyhow::anyhow!("failed to aquire lock of subscriptions: {err}"))
+}
+
+/// Format the error including its sources and a hint when it is a known TLS problem
+fn format_error(err: &(dyn std::error::Error + 'static)) -> String {
+    let mut text = err.to_string();
+    #[cfg_attr(not(feature = "tls"), allow(unused_mut))]
+    let mut hint = None;
+    let mut current = Some(err);
+    while let Some(err) = current {
+        let message = err.to_string();
+        if !text.contains(&message) {
+            text += ": ";
+            text += &message;
+        }
+        #[cfg(feature = "tls")]
+        if hint.is_none() {
+            hint = crate::mqtt::encryption::error_hint(err);
+        }
+        current = err.source();
+    }
+    if let Some(hint) = hint {
+        text += " (";
+        text += hint;
+        text += ")";
+    }
+    text
+}
+
+/// Process the notifications of the connection.
+/// The outcome of the first connection attempt is sent via `connected`, the thread ends when it failed.
 fn thread_logic(
     mut client: Client,
     mut connection: Connection,
-    subscribe_topic: &[String],
+    connected: mpsc::Sender<Result<(), ConnectionError>>,
     connection_err: &ConnectionErrorArc,
     history: &HistoryArc,
+    subscriptions: &SubscriptionsArc,
 ) {
+    let mut connected = Some(connected);
     for notification in connection.iter() {
         match notification {
             Ok(e) => {
                 *connection_err.write().unwrap() = None;
+                subscriptions.lock().unwrap().on_event(&e);
                 match e {
                     rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) => {
-                        for t in subscribe_topic {
-                            client.subscribe(t, QoS::ExactlyOnce).unwrap();
+                        // Failures are shown as the state of the subscriptions
+                        _ = subscriptions.lock().unwrap().resubscribe(&mut client);
+                        if let Some(connected) = connected.take() {
+                            _ = connected.send(Ok(()));
                         }
                     }
                     rumqttc::Event::Incoming(rumqttc::Packet::Publish(publish)) => {
                         if publish.dup {
                             continue;
                         }
-                        let time = Local::now();
-                        history.write().unwrap().add(&publish, time);
+                        history.write().unwrap().add(&publish, Local::now());
                     }
                     rumqttc::Event::Outgoing(rumqttc::Outgoing::Disconnect) => {
                         break;
@@ -113,9 +273,35 @@ fn thread_logic(
                 }
             }
             Err(err) => {
+                if let Some(connected) = connected.take() {
+                    _ = connected.send(Err(err));
+                    return;
+                }
                 *connection_err.write().unwrap() = Some(err);
                 sleep(Duration::from_millis(25));
             }
         };
     }
 }
+
+#[test]
+fn format_error_includes_sources() {
+    #[derive(Debug)]
+    struct Outer(std::io::Error);
+    impl std::fmt::Display for Outer {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("connection failed")
+        }
+    }
+    impl std::error::Error for Outer {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    let err = Outer(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "handshake failed",
+    ));
+    assert_eq!(format_error(&err), "connection failed: handshake failed");
+}
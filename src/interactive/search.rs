@@ -0,0 +1,265 @@
+use crossterm::event::KeyEvent;
+use regex::Regex;
+use tui::backend::Backend;
+use tui::layout::Rect;
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::Paragraph;
+use tui::Frame;
+use tui_textarea::TextArea;
+
+use crate::interactive::mqtt_history::HistoryView;
+use crate::mqtt::topic::{topic_filter_matches, TopicSet};
+use crate::mqtt::Payload;
+
+const STYLE_LABEL: Style = Style {
+    fg: Some(Color::Black),
+    bg: Some(Color::White),
+    add_modifier: Modifier::BOLD,
+    sub_modifier: Modifier::empty(),
+};
+const STYLE_ERROR: Style = Style {
+    fg: Some(Color::Red),
+    bg: None,
+    add_modifier: Modifier::BOLD,
+    sub_modifier: Modifier::empty(),
+};
+
+/// What the query is matched against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Target {
+    #[default]
+    Topic,
+    /// Latest payload of a topic
+    Payload,
+}
+
+impl Target {
+    const fn next(self) -> Self {
+        match self {
+            Self::Topic => Self::Payload,
+            Self::Payload => Self::Topic,
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Topic => "topics",
+            Self::Payload => "payloads",
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Substring(String),
+    /// MQTT topic filter with the `+` and `#` wildcards
+    TopicFilter(String),
+    Regex(Regex),
+}
+
+#[derive(Debug)]
+pub struct Query {
+    matcher: Matcher,
+    target: Target,
+}
+
+impl Query {
+    /// Topic queries with a `+` or `#` level are matched as MQTT topic filters, everything else by substring.
+    /// Returns `None` for an empty input.
+    pub fn parse(
+        input: &str,
+        is_regex: bool,
+        target: Target,
+    ) -> Result<Option<Self>, regex::Error> {
+        if input.is_empty() {
+            return Ok(None);
+        }
+        let matcher = if is_regex {
+            Matcher::Regex(Regex::new(input)?)
+        } else if target == Target::Topic
+            && input.split('/').any(|level| level == "+" || level == "#")
+        {
+            Matcher::TopicFilter(input.to_string())
+        } else {
+            Matcher::Substring(input.to_string())
+        };
+        Ok(Some(Self { matcher, target }))
+    }
+
+    pub fn matches(&self, topic: &str, latest: Option<&Payload>) -> bool {
+        match self.target {
+            Target::Topic => self.is_match(topic),
+            Target::Payload => match latest {
+                Some(Payload::String(str)) => self.is_match(str),
+                Some(Payload::Json(json)) => self.is_match(&json.dump()),
+                Some(Payload::NotUtf8(_)) | None => false,
+            },
+        }
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match &self.matcher {
+            Matcher::Substring(substring) => text.contains(substring.as_str()),
+            Matcher::TopicFilter(filter) => topic_filter_matches(filter, text),
+            Matcher::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+/// Search box of the topic overview. The query is updated while typing.
+#[derive(Default)]
+pub struct Search<'a> {
+    input: TextArea<'a>,
+    target: Target,
+    is_regex: bool,
+    query: Option<Query>,
+    /// The last valid query stays active while the regex is invalid
+    is_invalid: bool,
+    /// Hits of the query and the revision of the history they were searched in
+    hits: Option<(usize, TopicSet)>,
+}
+
+impl Search<'_> {
+    pub const fn get_query(&self) -> Option<&Query> {
+        self.query.as_ref()
+    }
+
+    /// Search the history again when it changed since the last search
+    pub fn update_hits(&mut self, history: &HistoryView) {
+        let revision = history.get_revision();
+        self.hits = self.query.as_ref().map(|query| match self.hits.take() {
+            Some((searched, hits)) if searched == revision => (searched, hits),
+            _ => (revision, TopicSet::new(history.search(query))),
+        });
+    }
+
+    /// Hits of the query as of the last [`Self::update_hits`]
+    pub fn get_hits(&self) -> Option<&TopicSet> {
+        self.hits.as_ref().map(|(_, hits)| hits)
+    }
+
+    /// Text of the active query shown when the search box is closed
+    pub fn get_filter_text(&self) -> Option<&str> {
+        self.query
+            .as_ref()
+            .and_then(|_| self.input.lines().first())
+            .map(String::as_str)
+    }
+
+    pub fn input(&mut self, key: KeyEvent) {
+        self.input.input(key);
+        self.update();
+    }
+
+    pub fn toggle_target(&mut self) {
+        self.target = self.target.next();
+        self.update();
+    }
+
+    pub fn toggle_regex(&mut self) {
+        self.is_regex = !self.is_regex;
+        self.update();
+    }
+
+    pub fn clear(&mut self) {
+        self.input = TextArea::default();
+        self.update();
+    }
+
+    fn update(&mut self) {
+        let input = self.input.lines().first().map_or("", String::as_str);
+        match Query::parse(input, self.is_regex, self.target) {
+            Ok(query) => {
+                self.query = query;
+                self.is_invalid = false;
+                self.hits = None;
+            }
+            Err(_) => self.is_invalid = true,
+        }
+    }
+
+    pub fn draw<B>(&self, f: &mut Frame<B>, area: Rect, hits: usize)
+    where
+        B: Backend,
+    {
+        let regex = if self.is_regex { " by regex" } else { "" };
+        let hit_count = self
+            .query
+            .as_ref()
+            .map_or_else(String::new, |_| format!(" ({hits} hits)"));
+        let label = format!(" Search {}{regex}{hit_count}: ", self.target.name());
+        let mut spans = vec![Span::styled(label, STYLE_LABEL)];
+        if self.is_invalid {
+            spans.push(Span::styled(" invalid regex ", STYLE_ERROR));
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        let label_width = Spans::from(spans.clone()).width() as u16;
+        f.render_widget(Paragraph::new(Spans::from(spans)), area);
+
+        let key_hints = Spans::from(vec![
+            Span::styled("Tab", STYLE_LABEL),
+            Span::from(format!(" Search {}  ", self.target.next().name())),
+            Span::styled("Ctrl+R", STYLE_LABEL),
+            Span::from(" Toggle regex  "),
+            Span::styled("Esc", STYLE_LABEL),
+            Span::from(" Clear  "),
+            Span::styled("Enter", STYLE_LABEL),
+            Span::from(" Keep filter"),
+        ]);
+        #[allow(clippy::cast_possible_truncation)]
+        let hints_width = key_hints.width() as u16;
+        let mut input_width = area.width.saturating_sub(label_width);
+        // Hints are only shown when there is enough room left to type
+        if input_width > hints_width + 20 {
+            input_width -= hints_width;
+            let hints_area = Rect {
+                x: area.x + area.width - hints_width,
+                width: hints_width,
+                ..area
+            };
+            f.render_widget(Paragraph::new(key_hints), hints_area);
+        }
+
+        let input_area = Rect {
+            x: area.x + label_width.min(area.width),
+            width: input_width,
+            ..area
+        };
+        f.render_widget(self.input.widget(), input_area);
+    }
+}
+
+#[test]
+fn query_kind_depends_on_input() {
+    let query = Query::parse("oo/ba", false, Target::Topic)
+        .unwrap()
+        .unwrap();
+    assert!(query.matches("foo/bar", None));
+
+    let query = Query::parse("foo/+", false, Target::Topic)
+        .unwrap()
+        .unwrap();
+    assert!(query.matches("foo/bar", None));
+    assert!(!query.matches("foo/bar/baz", None));
+
+    let query = Query::parse("^foo/b.r$", true, Target::Topic)
+        .unwrap()
+        .unwrap();
+    assert!(query.matches("foo/bar", None));
+    assert!(!query.matches("foo/bar/baz", None));
+
+    assert!(Query::parse("", false, Target::Topic).unwrap().is_none());
+    assert!(Query::parse("(", true, Target::Topic).is_err());
+}
+
+#[test]
+fn payload_query_matches_latest_payload() {
+    let query = Query::parse("\"on\"", false, Target::Payload)
+        .unwrap()
+        .unwrap();
+    let json = Payload::Json(json::object! {"state": "on"});
+    assert!(query.matches("foo", Some(&json)));
+    assert!(!query.matches("\"on\"", Some(&Payload::String("off".into()))));
+    assert!(!query.matches("\"on\"", None));
+}
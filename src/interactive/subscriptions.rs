@@ -0,0 +1,153 @@
+use crossterm::event::KeyEvent;
+use rumqttc::QoS;
+use tui::backend::Backend;
+use tui::layout::Rect;
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, List, ListItem, ListState};
+use tui::Frame;
+use tui_textarea::TextArea;
+
+use crate::interactive::ui::{focus_color, split_area_vertically};
+use crate::mqtt::subscription::{Subscription, SubscriptionState};
+
+/// Popup to add and remove subscriptions at runtime
+pub struct Subscriptions<'a> {
+    input: TextArea<'a>,
+    qos: QoS,
+    state: ListState,
+}
+
+impl Default for Subscriptions<'_> {
+    fn default() -> Self {
+        Self {
+            input: TextArea::default(),
+            qos: QoS::ExactlyOnce,
+            state: ListState::default(),
+        }
+    }
+}
+
+impl Subscriptions<'_> {
+    pub const fn get_qos(&self) -> QoS {
+        self.qos
+    }
+
+    pub fn next_qos(&mut self) {
+        self.qos = match self.qos {
+            QoS::AtMostOnce => QoS::AtLeastOnce,
+            QoS::AtLeastOnce => QoS::ExactlyOnce,
+            QoS::ExactlyOnce => QoS::AtMostOnce,
+        };
+    }
+
+    pub fn input(&mut self, key: KeyEvent) {
+        self.input.input(key);
+    }
+
+    /// Returns the entered filter and clears the input
+    pub fn take_filter(&mut self) -> Option<String> {
+        let filter = self.input.lines()[0].trim().to_string();
+        if filter.is_empty() {
+            return None;
+        }
+        self.input = TextArea::default();
+        Some(filter)
+    }
+
+    pub fn get_selected<'s>(&self, subscriptions: &'s [Subscription]) -> Option<&'s str> {
+        self.state
+            .selected()
+            .and_then(|index| subscriptions.get(index))
+            .map(|subscription| subscription.filter.as_str())
+    }
+
+    pub fn select_previous(&mut self) {
+        let index = self.state.selected().unwrap_or(0).saturating_sub(1);
+        self.state.select(Some(index));
+    }
+
+    pub fn select_next(&mut self, amount: usize) {
+        let index = self
+            .state
+            .selected()
+            .map_or(0, |index| index.saturating_add(1))
+            .min(amount.saturating_sub(1));
+        self.state.select(Some(index));
+    }
+
+    pub fn draw<B>(&mut self, f: &mut Frame<B>, subscriptions: &[Subscription])
+    where
+        B: Backend,
+    {
+        if subscriptions.is_empty() {
+            self.state.select(None);
+        } else {
+            let index = self
+                .state
+                .selected()
+                .unwrap_or(0)
+                .min(subscriptions.len() - 1);
+            self.state.select(Some(index));
+        }
+
+        let area = popup_area(f.size());
+        f.render_widget(Clear, area); // clear the background of the popup
+        let (list_area, input_area) = split_area_vertically(area, area.height.saturating_sub(3));
+
+        let focus_color = focus_color(true);
+        let items = subscriptions
+            .iter()
+            .map(|subscription| ListItem::new(subscription_line(subscription)))
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(focus_color))
+                    .title(format!("Subscriptions ({})", subscriptions.len())),
+            )
+            .highlight_style(Style::default().fg(Color::Black).bg(focus_color));
+        f.render_stateful_widget(list, list_area, &mut self.state);
+
+        self.input.set_block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(focus_color))
+                .title(format!("Subscribe with QoS {}", self.qos as u8)),
+        );
+        f.render_widget(self.input.widget(), input_area);
+    }
+}
+
+fn subscription_line(subscription: &Subscription) -> Spans<'_> {
+    let (state, color) = match &subscription.state {
+        SubscriptionState::Pending => ("pending".to_string(), Color::Yellow),
+        SubscriptionState::Granted(qos) => (format!("granted QoS {}", *qos as u8), Color::Green),
+        SubscriptionState::Rejected => ("rejected by broker".to_string(), Color::Red),
+        SubscriptionState::Error(err) => (format!("failed: {err}"), Color::Red),
+    };
+    Spans::from(vec![
+        Span::styled(
+            format!("QoS {}  ", subscription.qos as u8),
+            Style::default().add_modifier(Modifier::DIM),
+        ),
+        Span::raw(subscription.filter.clone()),
+        Span::raw("  "),
+        Span::styled(state, Style::default().fg(color)),
+    ])
+}
+
+fn popup_area(r: Rect) -> Rect {
+    // The order is important here. Clamp just panics on min > max which is not what is wanted.
+    #[allow(clippy::manual_clamp)]
+    let width = (r.width.saturating_mul(4) / 5)
+        .max(60)
+        .min(r.width.saturating_sub(4));
+    let height = (r.height.saturating_mul(3) / 5)
+        .max(10)
+        .min(r.height.saturating_sub(4));
+    let x = (r.width - width) / 2;
+    let y = (r.height - height) / 2;
+    Rect::new(x, y, width, height)
+}
@@ -1,5 +1,7 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
 
+use chrono::{DateTime, Local};
 use tui::backend::Backend;
 use tui::layout::Rect;
 use tui::style::{Color, Style};
@@ -7,48 +9,141 @@ use tui::widgets::{Block, Borders};
 use tui::Frame;
 use tui_tree_widget::{Tree, TreeItem, TreeState};
 
-use crate::interactive::mqtt_history::MqttHistory;
+use crate::interactive::mqtt_history::{HistoryView, SortOrder, TreeItemsView};
 use crate::interactive::ui::{focus_color, get_row_inside, CursorMove};
-use crate::mqtt::topic::get_parent;
+use crate::mqtt::topic::{get_parent, is_below, topic_filter_matches, TopicSet};
+
+/// Rules which topics are opened automatically when they first appear
+#[derive(Debug, Clone, Default)]
+pub struct AutoExpand {
+    /// Topics matching these filters are opened together with the topics above them
+    pub filters: Vec<String>,
+    /// Topics deeper than this amount of levels are not opened automatically.
+    /// Without filters all topics up to this depth are opened.
+    pub max_depth: Option<usize>,
+}
+
+impl AutoExpand {
+    const fn is_empty(&self) -> bool {
+        self.filters.is_empty() && self.max_depth.is_none()
+    }
+
+    fn matches(&self, topic: &str) -> bool {
+        let within_depth = self
+            .max_depth
+            .map_or(true, |max_depth| topic.split('/').count() <= max_depth);
+        let matches_filter = if self.filters.is_empty() {
+            self.max_depth.is_some()
+        } else {
+            self.filters
+                .iter()
+                .any(|filter| topic_filter_matches(filter, topic))
+        };
+        within_depth && matches_filter
+    }
+}
 
 #[derive(Default)]
 pub struct TopicOverview {
     last_area: Rect,
     opened_topics: HashSet<String>,
     selected_topic: Option<String>,
-    searched_topic: Option<HashSet<String>>,
     state: TreeState,
+    sort: SortOrder,
+    /// Message count of the topics when they were selected the last time or first appeared
+    seen: HashMap<String, usize>,
+    /// [`HistoryView::get_revision`] new topics were looked for the last time
+    seen_revision: Option<usize>,
+    auto_expand: AutoExpand,
+    /// Topics the [`AutoExpand`] rules were applied to already and whether they had topics below them then.
+    /// Closing them keeps them closed.
+    auto_expand_known: HashMap<String, bool>,
+    /// [`HistoryView::get_topic_amount`] the [`AutoExpand`] rules were applied the last time
+    auto_expand_topic_amount: Option<usize>,
 }
 
 impl TopicOverview {
+    pub fn new(auto_expand: AutoExpand) -> Self {
+        Self {
+            auto_expand,
+            ..Self::default()
+        }
+    }
+
     pub const fn get_opened(&self) -> &HashSet<String> {
         &self.opened_topics
     }
 
-    pub const fn get_query_items(&self) -> &Option<HashSet<String>> {
-        &self.searched_topic
+    pub const fn get_selected(&self) -> &Option<String> {
+        &self.selected_topic
     }
 
-    pub fn set_opened(&mut self, new_data: &HashSet<String>) {
-        self.searched_topic = Some(new_data.clone());
+    pub const fn get_sort(&self) -> SortOrder {
+        self.sort
     }
 
-    pub const fn get_selected(&self) -> &Option<String> {
-        &self.selected_topic
+    /// Messages of the selected topic are seen, the count of new messages starts again.
+    /// New topics count as seen when they appear so only the messages after that are new.
+    pub fn mark_seen(&mut self, history: &HistoryView) {
+        let revision = history.get_revision();
+        if self.seen_revision != Some(revision) {
+            self.seen_revision = Some(revision);
+            for topic in history.get_topics() {
+                let count = history.get_message_count(&topic);
+                self.seen.entry(topic).or_insert(count);
+            }
+        }
+        if let Some(topic) = &self.selected_topic {
+            self.seen
+                .insert(topic.clone(), history.get_message_count(topic));
+        }
     }
 
-    pub fn ensure_state(&mut self, history: &MqttHistory) {
+    /// `now` is the time of pausing while paused so the recently updated highlight does not fade
+    pub fn tree_items_view<'a>(
+        &'a self,
+        hits: Option<&'a TopicSet>,
+        alerting: &'a TopicSet,
+        now: DateTime<Local>,
+    ) -> TreeItemsView<'a> {
+        TreeItemsView {
+            hits,
+            alerting,
+            sort: self.sort,
+            opened: &self.opened_topics,
+            selected: self.selected_topic.as_deref(),
+            seen: &self.seen,
+            now,
+        }
+    }
+
+    /// The selection is kept on the same topic as it is remembered by its topic
+    pub fn next_sort(&mut self) {
+        self.sort = self.sort.next();
+    }
+
+    /// The topics above the search hits are shown opened
+    pub fn ensure_state(&mut self, history: &HistoryView, hits: Option<&TopicSet>) {
         self.state.close_all();
-        for topic in &self.opened_topics {
-            self.state
-                .open(history.get_tree_identifier(topic).unwrap_or_default());
+        let above_hits = hits.into_iter().flat_map(TopicSet::get_above);
+        for topic in self
+            .opened_topics
+            .iter()
+            .map(String::as_str)
+            .chain(above_hits)
+        {
+            self.state.open(
+                history
+                    .get_tree_identifier(topic, self.sort)
+                    .unwrap_or_default(),
+            );
         }
 
         // Ensure selected topic is selected index
         self.state.select(
             self.selected_topic
                 .as_ref()
-                .and_then(|selected_topic| history.get_tree_identifier(selected_topic))
+                .and_then(|selected_topic| history.get_tree_identifier(selected_topic, self.sort))
                 .unwrap_or_default(),
         );
     }
@@ -59,11 +154,18 @@ impl TopicOverview {
         area: Rect,
         topic_amount: usize,
         tree_items: &[TreeItem],
+        filter: Option<&str>,
         has_focus: bool,
     ) where
         B: Backend,
     {
-        let title = format!("Topics ({topic_amount})");
+        let mut title = format!("Topics ({topic_amount})");
+        if let Some(filter) = filter {
+            _ = write!(title, " filtered by {filter:?}");
+        }
+        if self.sort != SortOrder::Alphabetical {
+            _ = write!(title, " sorted by {}", self.sort.name());
+        }
         let focus_color = focus_color(has_focus);
         let widget = Tree::new(tree_items)
             .block(
@@ -98,14 +200,87 @@ impl TopicOverview {
         different
     }
 
+    /// Apply the [`AutoExpand`] rules to the topics which appeared since the last call.
+    /// Matching leaves can not be opened themselves but the topics above them are.
+    pub fn auto_expand(&mut self, history: &HistoryView) {
+        if self.auto_expand.is_empty() {
+            return;
+        }
+        // Topics are only looked at again when new ones appeared
+        let topic_amount = history.get_topic_amount();
+        if self.auto_expand_topic_amount == Some(topic_amount) {
+            return;
+        }
+        self.auto_expand_topic_amount = Some(topic_amount);
+
+        let expandable = history.get_expandable_topics(None);
+        let leaves = {
+            let expandable = expandable.iter().collect::<HashSet<_>>();
+            history
+                .get_topics()
+                .into_iter()
+                .filter(|topic| !expandable.contains(topic))
+                .collect::<Vec<_>>()
+        };
+        let topics = expandable
+            .into_iter()
+            .map(|topic| (topic, true))
+            .chain(leaves.into_iter().map(|topic| (topic, false)));
+        for (topic, is_expandable) in topics {
+            let known = self.auto_expand_known.get(&topic).copied();
+            // Leaves are looked at again once topics appear below them
+            if known == Some(true) || (known.is_some() && !is_expandable) {
+                continue;
+            }
+            // The rules do not know about the broker level of the merged view
+            let matches = history
+                .strip_broker(&topic)
+                .map_or(false, |topic| self.auto_expand.matches(topic));
+            if matches {
+                if known.is_none() {
+                    self.open_above(&topic);
+                }
+                if is_expandable {
+                    self.opened_topics.insert(topic.clone());
+                }
+            }
+            self.auto_expand_known.insert(topic, is_expandable);
+        }
+    }
+
+    fn open_above(&mut self, topic: &str) {
+        let mut above = get_parent(topic);
+        while let Some(parent) = above {
+            self.opened_topics.insert(parent.to_string());
+            above = get_parent(parent);
+        }
+    }
+
+    /// Select the topic and open the topics above it to show it
+    pub fn select_topic(&mut self, topic: &str) {
+        self.open_above(topic);
+        self.selected_topic = Some(topic.to_string());
+    }
+
     pub fn open(&mut self) {
         if let Some(topic) = &self.selected_topic {
-            if "gps/v1".contains(topic)
-                || topic.contains("gps/v1")
-                || "gps/v1/e/867378033978818123".contains(topic)
-            {
-                self.opened_topics.insert(topic.clone());
-            }
+            self.opened_topics.insert(topic.clone());
+        }
+    }
+
+    /// Open the selected topic and all topics below it
+    pub fn expand_all(&mut self, history: &HistoryView) {
+        if let Some(topic) = &self.selected_topic {
+            self.opened_topics
+                .extend(history.get_expandable_topics(Some(topic)));
+        }
+    }
+
+    /// Close the selected topic and all topics below it
+    pub fn collapse_all(&mut self) {
+        if let Some(selected) = &self.selected_topic {
+            self.opened_topics
+                .retain(|topic| topic != selected && !is_below(topic, selected));
         }
     }
 
@@ -137,3 +312,175 @@ impl TopicOverview {
         }
     }
 }
+
+#[test]
+fn topics_are_seen_when_they_appear() {
+    let mut history = crate::interactive::mqtt_history::MqttHistory::example();
+    let mut overview = TopicOverview::default();
+    overview.mark_seen(&history.view());
+    let publish = rumqttc::Publish::new("foo/test", rumqttc::QoS::AtLeastOnce, "F");
+    history.add(&publish, chrono::Local::now());
+    history.add(
+        &rumqttc::Publish::new("new", rumqttc::QoS::AtLeastOnce, "1"),
+        chrono::Local::now(),
+    );
+    overview.mark_seen(&history.view());
+    assert_eq!(overview.seen.get("foo/test"), Some(&1));
+    assert_eq!(overview.seen.get("new"), Some(&1));
+    assert_eq!(overview.seen.len(), 4);
+}
+
+#[test]
+fn auto_expand_by_filter_opens_topics_above() {
+    let mut history = crate::interactive::mqtt_history::MqttHistory::example();
+    history.add(
+        &rumqttc::Publish::new("foo/bar/baz", rumqttc::QoS::AtLeastOnce, "E"),
+        chrono::Local::now(),
+    );
+    let mut overview = TopicOverview::new(AutoExpand {
+        filters: vec!["+/bar".to_string()],
+        max_depth: None,
+    });
+    overview.auto_expand(&history.view());
+    let mut opened = overview.get_opened().iter().collect::<Vec<_>>();
+    opened.sort();
+    assert_eq!(opened, ["foo", "foo/bar"]);
+
+    // Closed topics stay closed
+    overview.selected_topic = Some("foo".to_string());
+    overview.collapse_all();
+    overview.auto_expand(&history.view());
+    assert!(overview.get_opened().is_empty());
+}
+
+#[test]
+fn auto_expand_by_filter_matching_a_leaf_opens_topics_above() {
+    let mut history = crate::interactive::mqtt_history::MqttHistory::new();
+    for topic in ["home/kitchen/temp", "home/living/temp"] {
+        history.add(
+            &rumqttc::Publish::new(topic, rumqttc::QoS::AtLeastOnce, "1"),
+            chrono::Local::now(),
+        );
+    }
+    let mut overview = TopicOverview::new(AutoExpand {
+        filters: vec!["home/kitchen/temp".to_string()],
+        max_depth: None,
+    });
+    overview.auto_expand(&history.view());
+    let mut opened = overview.get_opened().iter().collect::<Vec<_>>();
+    opened.sort();
+    assert_eq!(opened, ["home", "home/kitchen"]);
+
+    // Once the leaf has topics below it, it is opened too
+    history.add(
+        &rumqttc::Publish::new("home/kitchen/temp/raw", rumqttc::QoS::AtLeastOnce, "1"),
+        chrono::Local::now(),
+    );
+    overview.auto_expand(&history.view());
+    let mut opened = overview.get_opened().iter().collect::<Vec<_>>();
+    opened.sort();
+    assert_eq!(opened, ["home", "home/kitchen", "home/kitchen/temp"]);
+}
+
+#[test]
+fn auto_expand_applies_to_topics_hidden_while_paused_after_resuming() {
+    use crate::interactive::mqtt_history::{HistoryView, MqttHistory};
+    let received = chrono::Local::now();
+    let mut history = MqttHistory::new();
+    history.add(
+        &rumqttc::Publish::new("home/kitchen/temp", rumqttc::QoS::AtLeastOnce, "1"),
+        received,
+    );
+    let mut overview = TopicOverview::new(AutoExpand {
+        filters: vec!["home/kitchen/temp".to_string()],
+        max_depth: None,
+    });
+    let paused = received - chrono::Duration::seconds(1);
+    overview.auto_expand(&HistoryView::new(vec![(None, &history)], Some(paused)));
+    assert!(overview.get_opened().is_empty());
+
+    overview.auto_expand(&history.view());
+    let mut opened = overview.get_opened().iter().collect::<Vec<_>>();
+    opened.sort();
+    assert_eq!(opened, ["home", "home/kitchen"]);
+}
+
+#[test]
+fn auto_expand_ignores_broker_level_of_merged_view() {
+    use crate::interactive::mqtt_history::{HistoryView, MqttHistory};
+    let mut a = MqttHistory::new();
+    let mut b = MqttHistory::new();
+    for history in [&mut a, &mut b] {
+        history.add(
+            &rumqttc::Publish::new("home/kitchen/temp", rumqttc::QoS::AtLeastOnce, "1"),
+            chrono::Local::now(),
+        );
+    }
+    let merged = HistoryView::new(vec![(Some("a"), &a), (Some("b"), &b)], None);
+
+    let mut overview = TopicOverview::new(AutoExpand {
+        filters: vec!["home/+".to_string()],
+        max_depth: None,
+    });
+    overview.auto_expand(&merged);
+    let mut opened = overview.get_opened().iter().collect::<Vec<_>>();
+    opened.sort();
+    assert_eq!(
+        opened,
+        [
+            "a",
+            "a/home",
+            "a/home/kitchen",
+            "b",
+            "b/home",
+            "b/home/kitchen"
+        ]
+    );
+
+    let mut overview = TopicOverview::new(AutoExpand {
+        filters: vec![],
+        max_depth: Some(1),
+    });
+    overview.auto_expand(&merged);
+    let mut opened = overview.get_opened().iter().collect::<Vec<_>>();
+    opened.sort();
+    assert_eq!(opened, ["a", "a/home", "b", "b/home"]);
+}
+
+#[test]
+fn auto_expand_respects_max_depth() {
+    let rules = AutoExpand {
+        filters: vec![],
+        max_depth: Some(2),
+    };
+    assert!(rules.matches("foo/bar"));
+    assert!(!rules.matches("foo/bar/baz"));
+    assert!(!AutoExpand::default().matches("foo"));
+
+    let rules = AutoExpand {
+        filters: vec!["foo/#".to_string()],
+        max_depth: Some(1),
+    };
+    assert!(rules.matches("foo"));
+    assert!(!rules.matches("foo/bar"));
+    assert!(!rules.matches("bar"));
+}
+
+#[test]
+fn expand_all_opens_subtree() {
+    let mut history = crate::interactive::mqtt_history::MqttHistory::example();
+    history.add(
+        &rumqttc::Publish::new("foo/bar/baz", rumqttc::QoS::AtLeastOnce, "E"),
+        chrono::Local::now(),
+    );
+    let mut overview = TopicOverview {
+        selected_topic: Some("foo".to_string()),
+        ..TopicOverview::default()
+    };
+    overview.expand_all(&history.view());
+    assert_eq!(overview.get_opened().len(), 2);
+    overview.selected_topic = Some("foo/bar".to_string());
+    overview.collapse_all();
+    let opened = overview.get_opened().iter().collect::<Vec<_>>();
+    assert_eq!(opened, ["foo"]);
+}
@@ -16,6 +16,12 @@ pub const STYLE_BOLD: Style = Style {
     add_modifier: Modifier::BOLD,
     sub_modifier: Modifier::empty(),
 };
+pub const STYLE_ALERT: Style = Style {
+    fg: Some(Color::Red),
+    bg: None,
+    add_modifier: Modifier::BOLD,
+    sub_modifier: Modifier::empty(),
+};
 
 pub const fn focus_color(has_focus: bool) -> Color {
     if has_focus {
@@ -0,0 +1,315 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use chrono::{DateTime, Local};
+use tui::backend::Backend;
+use tui::layout::{Constraint, Rect};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Borders, Row, Table, TableState};
+use tui::Frame;
+
+use crate::interactive::details::parse_y;
+use crate::interactive::mqtt_history::{Entries, HistoryView, STYLE_DARKGRAY};
+use crate::interactive::ui::{focus_color, split_area_vertically};
+use crate::mqtt::Payload;
+
+/// Amount of the newest values shown in the sparkline
+const SPARKLINE_VALUES: u16 = 12;
+const SPARKLINE_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// `mqttui/watchlist` in the config directory of the user
+pub fn default_file() -> Option<PathBuf> {
+    let config_dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    config_dir.map(|dir| dir.join("mqttui").join("watchlist"))
+}
+
+/// Pins of all tabs. Each pin is the topic prefixed with the tree label of its broker.
+#[derive(Default)]
+pub struct Pins {
+    topics: Vec<String>,
+    /// Pins are written to this file to keep them over restarts
+    file: Option<PathBuf>,
+    /// Saving is retried on the next change, the error is shown until then
+    save_error: Option<String>,
+}
+
+pub type PinsRc = Rc<RefCell<Pins>>;
+
+impl Pins {
+    /// Load the pins from the file when it exists already
+    pub fn load(file: Option<&Path>) -> anyhow::Result<PinsRc> {
+        let topics = file.map(read_topics).transpose()?.unwrap_or_default();
+        Ok(Rc::new(RefCell::new(Self {
+            topics,
+            file: file.map(Path::to_path_buf),
+            save_error: None,
+        })))
+    }
+
+    fn toggle(&mut self, topic: &str) {
+        toggle_topic(&mut self.topics, topic);
+        if let Some(file) = &self.file {
+            let mut content = self.topics.join("\n");
+            content.push('\n');
+            let result = file
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::write(file, content));
+            self.save_error = result.err().map(|err| err.to_string());
+        }
+    }
+}
+
+/// Topics pinned by the user to keep an eye on them wherever they are in the tree
+pub struct Watchlist {
+    pins: PinsRc,
+    /// Tree label of the broker of this tab. The merged tab has none as its topics start with the label already.
+    broker: Option<String>,
+    state: TableState,
+}
+
+impl Watchlist {
+    pub fn new(pins: PinsRc, broker: Option<String>) -> Self {
+        Self {
+            pins,
+            broker,
+            state: TableState::default(),
+        }
+    }
+
+    /// Topics pinned in this tab as they are named in its tree
+    fn topics(&self) -> Vec<String> {
+        let pins = self.pins.borrow();
+        match &self.broker {
+            None => pins.topics.clone(),
+            Some(broker) => pins
+                .topics
+                .iter()
+                .filter_map(|pin| pin.strip_prefix(broker.as_str())?.strip_prefix('/'))
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.topics().is_empty()
+    }
+
+    /// Pin the topic or unpin it when it is pinned already. Pins are shared with the other tabs.
+    pub fn toggle(&mut self, topic: &str) {
+        let pin = self
+            .broker
+            .as_ref()
+            .map_or_else(|| topic.to_string(), |broker| format!("{broker}/{topic}"));
+        self.pins.borrow_mut().toggle(&pin);
+    }
+
+    pub fn get_selected(&self) -> Option<String> {
+        self.state
+            .selected()
+            .and_then(|index| self.topics().into_iter().nth(index))
+    }
+
+    pub fn select_previous(&mut self) {
+        let index = self.state.selected().unwrap_or(0).saturating_sub(1);
+        self.state.select(Some(index));
+    }
+
+    pub fn select_next(&mut self) {
+        let index = self
+            .state
+            .selected()
+            .map_or(0, |index| index.saturating_add(1))
+            .min(self.topics().len().saturating_sub(1));
+        self.state.select(Some(index));
+    }
+
+    /// Draw at the bottom of the area when something is pinned. Returns the area left above.
+    pub fn draw<B>(
+        &mut self,
+        f: &mut Frame<B>,
+        area: Rect,
+        history: &HistoryView,
+        has_focus: bool,
+    ) -> Rect
+    where
+        B: Backend,
+    {
+        let topics = self.topics();
+        if topics.is_empty() {
+            self.state.select(None);
+            return area;
+        }
+        let index = self.state.selected().unwrap_or(0).min(topics.len() - 1);
+        self.state.select(Some(index));
+
+        // Header and borders need 3 lines
+        let height = u16::try_from(topics.len())
+            .unwrap_or(u16::MAX)
+            .saturating_add(3)
+            .min(area.height / 3);
+        let (remaining_area, area) = split_area_vertically(area, area.height - height);
+
+        let now = Local::now();
+        let rows = topics.iter().map(|topic| {
+            let entries = history.get(topic);
+            let latest = entries.and_then(Entries::back);
+            let value = latest.map_or_else(String::new, |entry| payload_value(&entry.payload));
+            let age = latest.map_or_else(String::new, |entry| format_age(entry.received, now));
+            let mut values = entries
+                .into_iter()
+                .flatten()
+                .rev()
+                .filter_map(|entry| parse_y(&entry.payload, &[]))
+                .take(SPARKLINE_VALUES.into())
+                .collect::<Vec<_>>();
+            values.reverse();
+            Row::new(vec![topic.clone(), value, age, sparkline(&values)])
+        });
+
+        let title = self.pins.borrow().save_error.as_ref().map_or_else(
+            || format!("Watchlist ({})", topics.len()),
+            |err| format!("Watchlist ({}) Saving failed: {err}", topics.len()),
+        );
+        let focus_color = focus_color(has_focus);
+        let table = Table::new(rows)
+            .header(Row::new(vec!["Topic", "Value", "Age", "Trend"]).style(STYLE_DARKGRAY))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(focus_color))
+                    .title(title),
+            )
+            .highlight_style(Style::default().fg(Color::Black).bg(focus_color))
+            .widths(&[
+                Constraint::Percentage(45),
+                Constraint::Percentage(30),
+                Constraint::Length(4),
+                Constraint::Length(SPARKLINE_VALUES),
+            ]);
+        f.render_stateful_widget(table, area, &mut self.state);
+        remaining_area
+    }
+}
+
+fn toggle_topic(topics: &mut Vec<String>, topic: &str) {
+    if let Some(index) = topics.iter().position(|o| o == topic) {
+        topics.remove(index);
+    } else {
+        topics.push(topic.to_string());
+    }
+}
+
+/// One topic per line
+fn parse_topics(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+fn read_topics(file: &Path) -> anyhow::Result<Vec<String>> {
+    match fs::read_to_string(file) {
+        Ok(content) => Ok(parse_topics(&content)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(anyhow::anyhow!(
+            "failed to read watchlist {}: {err}",
+            file.display()
+        )),
+    }
+}
+
+fn payload_value(payload: &Payload) -> String {
+    match payload {
+        Payload::NotUtf8(_) => "Payload not UTF-8".to_string(),
+        Payload::String(str) => str.to_string(),
+        Payload::Json(json) => json.dump(),
+    }
+}
+
+/// Short age like `42s` or `3h`
+pub fn format_age(time: DateTime<Local>, now: DateTime<Local>) -> String {
+    let seconds = (now - time).num_seconds().max(0);
+    match seconds {
+        0..=59 => format!("{seconds}s"),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+/// One bar per value scaled between the minimum and maximum of them
+fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+    values
+        .iter()
+        .map(|value| {
+            if range > 0.0 {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let index = ((value - min) / range * 7.0).round() as usize;
+                SPARKLINE_BARS[index.min(7)]
+            } else {
+                SPARKLINE_BARS[0]
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn sparkline_works() {
+    assert_eq!(sparkline(&[]), "");
+    assert_eq!(sparkline(&[1.0, 1.0]), "▁▁");
+    assert_eq!(sparkline(&[0.0, 7.0, 3.5, 1.0]), "▁█▅▂");
+}
+
+#[test]
+fn format_age_works() {
+    let now = Local::now();
+    assert_eq!(format_age(now, now), "0s");
+    assert_eq!(format_age(now - chrono::Duration::seconds(61), now), "1m");
+    assert_eq!(format_age(now - chrono::Duration::hours(5), now), "5h");
+    assert_eq!(format_age(now - chrono::Duration::days(2), now), "2d");
+    assert_eq!(format_age(now + chrono::Duration::seconds(3), now), "0s");
+}
+
+#[test]
+fn topics_are_toggled() {
+    let mut topics = parse_topics("foo/bar\n\n  baz \n");
+    assert_eq!(topics, ["foo/bar", "baz"]);
+    toggle_topic(&mut topics, "test");
+    toggle_topic(&mut topics, "foo/bar");
+    assert_eq!(topics, ["baz", "test"]);
+}
+
+#[test]
+fn pins_are_shared_between_broker_and_merged_tabs() {
+    let pins = Pins::load(None).unwrap();
+    let mut broker = Watchlist::new(Rc::clone(&pins), Some("mqtt:localhost:1883".to_string()));
+    let mut other = Watchlist::new(Rc::clone(&pins), Some("mqtt:other:1883".to_string()));
+    let mut merged = Watchlist::new(pins, None);
+    broker.toggle("foo/bar");
+    assert_eq!(broker.topics(), ["foo/bar"]);
+    assert!(other.is_empty());
+    assert_eq!(merged.topics(), ["mqtt:localhost:1883/foo/bar"]);
+    merged.toggle("mqtt:other:1883/baz");
+    assert_eq!(other.topics(), ["baz"]);
+    merged.toggle("mqtt:localhost:1883/foo/bar");
+    assert!(broker.is_empty());
+    other.toggle("baz");
+    assert!(merged.is_empty());
+}
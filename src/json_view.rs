@@ -1,6 +1,39 @@
 use json::JsonValue;
+use tui::style::{Color, Modifier, Style};
 use tui_tree_widget::TreeItem;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Change {
+    const fn style(self) -> Style {
+        match self {
+            Self::Added => Style {
+                fg: Some(Color::LightGreen),
+                bg: None,
+                add_modifier: Modifier::empty(),
+                sub_modifier: Modifier::empty(),
+            },
+            Self::Removed => Style {
+                fg: Some(Color::LightRed),
+                bg: None,
+                add_modifier: Modifier::CROSSED_OUT,
+                sub_modifier: Modifier::empty(),
+            },
+            Self::Changed => Style {
+                fg: Some(Color::Yellow),
+                bg: None,
+                add_modifier: Modifier::empty(),
+                sub_modifier: Modifier::empty(),
+            },
+        }
+    }
+}
+
 fn get_nth_subvalue(root: &JsonValue, select: usize) -> Option<&JsonValue> {
     match root {
         JsonValue::Object(object) => object.iter().nth(select).map(|(_key, value)| value),
@@ -21,6 +54,27 @@ pub fn get_selected_subvalue<'a>(
     Some(current)
 }
 
+/// Keys and array indices leading to the selected subvalue joined with dots, like `a.0.b`
+pub fn get_selected_path(root: &JsonValue, selection: &[usize]) -> String {
+    let mut current = root;
+    let mut path = Vec::with_capacity(selection.len());
+    for select in selection {
+        let key = match current {
+            JsonValue::Object(object) => object.iter().nth(*select).map(|(key, _)| key.to_string()),
+            JsonValue::Array(_) => Some(select.to_string()),
+            _ => None,
+        };
+        match (key, get_nth_subvalue(current, *select)) {
+            (Some(key), Some(next)) => {
+                path.push(key);
+                current = next;
+            }
+            _ => break,
+        }
+    }
+    path.join(".")
+}
+
 pub fn root_tree_items_from_json(root: &JsonValue) -> Vec<TreeItem<'_>> {
     match root {
         JsonValue::Object(object) => tree_items_from_json_object(object),
@@ -29,6 +83,116 @@ pub fn root_tree_items_from_json(root: &JsonValue) -> Vec<TreeItem<'_>> {
     }
 }
 
+/// Tree of `root` with its differences to `base` highlighted and the paths of the differences.
+///
+/// Removed entries are appended after the children of `root` so the identifiers of the tree stay valid for `root`.
+/// Objects and arrays containing differences are highlighted as changed but only the differences themselves are returned as changes.
+pub fn root_tree_items_from_json_diff<'a>(
+    root: &'a JsonValue,
+    base: &'a JsonValue,
+) -> (Vec<TreeItem<'a>>, Vec<(String, Change)>) {
+    let mut changes = Vec::new();
+    let items = if root == base {
+        root_tree_items_from_json(root)
+    } else if is_same_container(root, base) {
+        diff_children("", root, base, &mut changes)
+    } else {
+        changes.push((String::new(), Change::Changed));
+        root_tree_items_from_json(root)
+            .into_iter()
+            .map(|item| item.style(Change::Changed.style()))
+            .collect()
+    };
+    (items, changes)
+}
+
+/// The tree of `root` or of its differences to `base`
+pub fn tree_items<'a>(root: &'a JsonValue, base: Option<&'a JsonValue>) -> Vec<TreeItem<'a>> {
+    base.map_or_else(
+        || root_tree_items_from_json(root),
+        |base| root_tree_items_from_json_diff(root, base).0,
+    )
+}
+
+const fn is_same_container(a: &JsonValue, b: &JsonValue) -> bool {
+    matches!(
+        (a, b),
+        (JsonValue::Object(_), JsonValue::Object(_)) | (JsonValue::Array(_), JsonValue::Array(_))
+    )
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Children of `root` and the removed ones of `base`. Both have to be the same kind of container.
+fn diff_children<'a>(
+    path: &str,
+    root: &'a JsonValue,
+    base: &'a JsonValue,
+    changes: &mut Vec<(String, Change)>,
+) -> Vec<TreeItem<'a>> {
+    let (current, removed): (Vec<_>, Vec<_>) = match (root, base) {
+        (JsonValue::Object(root), JsonValue::Object(base)) => (
+            root.iter()
+                .map(|(key, value)| (key.to_string(), value, base.get(key)))
+                .collect(),
+            base.iter()
+                .filter(|(key, _)| root.get(key).is_none())
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        ),
+        (JsonValue::Array(root), JsonValue::Array(base)) => (
+            root.iter()
+                .enumerate()
+                .map(|(index, value)| (index.to_string(), value, base.get(index)))
+                .collect(),
+            base.iter()
+                .enumerate()
+                .skip(root.len())
+                .map(|(index, value)| (index.to_string(), value))
+                .collect(),
+        ),
+        _ => (Vec::new(), Vec::new()),
+    };
+
+    let mut items = current
+        .into_iter()
+        .map(|(key, value, base)| {
+            let path = join_path(path, &key);
+            match base {
+                Some(base) if base == value => tree_items_from_json(&key, value),
+                Some(base) if is_same_container(value, base) => {
+                    let children = diff_children(&path, value, base, changes);
+                    TreeItem::new(key, children).style(Change::Changed.style())
+                }
+                Some(base) => {
+                    changes.push((path, Change::Changed));
+                    let item = if matches!(value, JsonValue::Object(_) | JsonValue::Array(_)) {
+                        tree_items_from_json(&key, value)
+                    } else {
+                        TreeItem::new_leaf(format!("{key}: {value} (was {base})"))
+                    };
+                    item.style(Change::Changed.style())
+                }
+                None => {
+                    changes.push((path, Change::Added));
+                    tree_items_from_json(&key, value).style(Change::Added.style())
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    for (key, value) in removed {
+        changes.push((join_path(path, &key), Change::Removed));
+        items.push(tree_items_from_json(&key, value).style(Change::Removed.style()));
+    }
+    items
+}
+
 fn tree_items_from_json<'a>(key: &str, value: &'a JsonValue) -> TreeItem<'a> {
     match value {
         JsonValue::Object(object) => {
@@ -124,3 +288,41 @@ fn can_get_selected_value() {
     let result = get_selected_subvalue(&root, &[1, 1]);
     assert_eq!(result, Some(&JsonValue::Boolean(true)));
 }
+
+#[test]
+fn selected_path_works() {
+    let root = json::parse(r#"{"a": [1, {"b": 2}], "c": 3}"#).unwrap();
+    assert_eq!(get_selected_path(&root, &[0, 1, 0]), "a.1.b");
+    assert_eq!(get_selected_path(&root, &[1]), "c");
+    assert_eq!(get_selected_path(&root, &[]), "");
+    assert_eq!(get_selected_path(&root, &[1, 0]), "c");
+}
+
+#[test]
+fn diff_finds_changes() {
+    let base =
+        json::parse(r#"{"a": 1, "b": {"c": true, "d": "x"}, "e": [1, 2, 3], "f": 0}"#).unwrap();
+    let root =
+        json::parse(r#"{"a": 1, "b": {"c": false, "d": "x"}, "e": [1, 5], "g": null}"#).unwrap();
+    let (items, changes) = root_tree_items_from_json_diff(&root, &base);
+    // The removed "f" is appended
+    assert_eq!(items.len(), 5);
+    assert_eq!(
+        changes,
+        [
+            ("b.c".to_string(), Change::Changed),
+            ("e.1".to_string(), Change::Changed),
+            ("e.2".to_string(), Change::Removed),
+            ("g".to_string(), Change::Added),
+            ("f".to_string(), Change::Removed),
+        ]
+    );
+}
+
+#[test]
+fn diff_of_equal_or_different_kinds() {
+    let root = json::parse(r#"{"a": 1}"#).unwrap();
+    assert!(root_tree_items_from_json_diff(&root, &root).1.is_empty());
+    let (_, changes) = root_tree_items_from_json_diff(&root, &JsonValue::from(42));
+    assert_eq!(changes, [(String::new(), Change::Changed)]);
+}
@@ -5,17 +5,26 @@ use chrono::Local;
 use rumqttc::Connection;
 
 use crate::format;
+use crate::mqtt::subscription::SubscriptionTracker;
 use crate::mqtt::{Payload, Time};
 
-pub fn show(mut connection: Connection, verbose: bool) {
+pub fn show(
+    mut connection: Connection,
+    mut subscriptions: SubscriptionTracker,
+    verbose: bool,
+) -> anyhow::Result<()> {
     for notification in connection.iter() {
-        if let rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) =
-            notification.expect("connection error")
-        {
+        let notification = notification.expect("connection error");
+        subscriptions.on_event(&notification);
+        if let rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) = notification {
             break;
         }
     }
     for notification in connection.iter() {
+        if let Ok(event) = &notification {
+            subscriptions.on_event(event);
+            subscriptions.ensure_none_rejected()?;
+        }
         match notification {
             Ok(rumqttc::Event::Outgoing(outgoing)) => {
                 if verbose {
@@ -53,4 +62,5 @@ pub fn show(mut connection: Connection, verbose: bool) {
             }
         }
     }
+    Ok(())
 }
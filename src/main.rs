@@ -4,7 +4,9 @@ use std::time::Duration;
 
 use clap::Parser;
 use cli::SubCommands;
-use rumqttc::{self, Client, MqttOptions, QoS, Transport};
+use mqtt::subscription::SubscriptionTracker;
+use mqtt::ServerCertificateArc;
+use rumqttc::{self, Client, Connection, MqttOptions, ProxyType, QoS, Transport};
 
 mod clean_retained;
 mod cli;
@@ -19,89 +21,266 @@ mod read_one;
 fn main() -> anyhow::Result<()> {
     let matches = cli::Cli::parse();
 
-    let (mut client, connection) = {
-        let (transport, host, port) = match &matches.broker {
-            cli::Broker::Tcp { host, port } => (Transport::Tcp, host.clone(), *port),
-            #[cfg(feature = "tls")]
-            cli::Broker::Ssl { host, port } => (
-                Transport::Tls(mqtt::encryption::create_tls_configuration(
-                    matches.insecure,
-                    &matches.client_cert,
-                    &matches.client_key,
-                )?),
-                host.clone(),
-                *port,
-            ),
-            // On WebSockets the port is ignored. See https://github.com/bytebeamio/rumqtt/issues/270
-            #[cfg(feature = "tls")]
-            cli::Broker::WebSocket(url) => (Transport::Ws, url.to_string(), 666),
-            #[cfg(feature = "tls")]
-            cli::Broker::WebSocketSsl(url) => (
-                Transport::Wss(mqtt::encryption::create_tls_configuration(
-                    matches.insecure,
-                    &matches.client_cert,
-                    &matches.client_key,
-                )?),
-                url.to_string(),
-                666,
-            ),
-        };
-
-        let client_id = matches
-            .client_id
-            .unwrap_or_else(|| format!("mqttui-{:x}", rand::random::<u32>()));
-
-        let mut mqttoptions = MqttOptions::new(client_id, host, port);
-        mqttoptions.set_max_packet_size(usize::MAX, usize::MAX);
-        mqttoptions.set_transport(transport);
-
-        if let Some(password) = matches.password {
-            let username = matches.username.unwrap();
-            mqttoptions.set_credentials(username, password);
-        }
-
-        if let Some(SubCommands::CleanRetained { timeout, .. }) = matches.subcommands {
-            mqttoptions.set_keep_alive(Duration::from_secs_f32(timeout));
-        }
+    #[cfg(feature = "tls")]
+    anyhow::ensure!(
+        matches.ws_header.is_empty() || matches.broker.iter().any(cli::Broker::is_websocket),
+        "--ws-header can only be used with ws:// or wss:// brokers"
+    );
 
-        Client::new(mqttoptions, 10)
+    let subcommand = match &matches.subcommands {
+        Some(subcommand) => subcommand.clone(),
+        None => return show_interactive(&matches),
     };
 
-    match matches.subcommands {
-        Some(SubCommands::CleanRetained { topic, dry_run, .. }) => {
-            client.subscribe(topic, QoS::AtLeastOnce)?;
-            clean_retained::clean_retained(client, connection, dry_run);
+    anyhow::ensure!(
+        matches.broker.len() == 1,
+        "Multiple brokers are only supported in the interactive mode"
+    );
+    let (mut client, connection, _, _relay) = connect(&matches, &matches.broker[0], 0)?;
+
+    match subcommand {
+        SubCommands::CleanRetained { topic, dry_run, .. } => {
+            let subscriptions = subscribe(&mut client, vec![topic])?;
+            clean_retained::clean_retained(client, connection, subscriptions, dry_run)?;
         }
-        Some(SubCommands::Log { topic, verbose }) => {
-            for topic in topic {
-                client.subscribe(topic, QoS::AtLeastOnce)?;
-            }
-            log::show(connection, verbose);
+        SubCommands::Log { topic, verbose } => {
+            let subscriptions = subscribe(&mut client, topic)?;
+            log::show(connection, subscriptions, verbose)?;
         }
-        Some(SubCommands::ReadOne {
+        SubCommands::ReadOne {
             topic,
             ignore_retained,
-        }) => {
-            for topic in topic {
-                client.subscribe(topic, QoS::AtLeastOnce)?;
-            }
-            read_one::show(client, connection, ignore_retained);
+        } => {
+            let subscriptions = subscribe(&mut client, topic)?;
+            read_one::show(client, connection, subscriptions, ignore_retained)?;
         }
-        Some(SubCommands::Publish {
+        SubCommands::Publish {
             topic,
             payload,
             retain,
             verbose,
-        }) => {
+        } => {
             client.publish(topic, QoS::AtLeastOnce, retain, payload)?;
             publish::eventloop(client, connection, verbose);
         }
-        None => {
-            let broker = matches.broker;
-            interactive::show(client.clone(), connection, &broker, matches.topic)?;
-            client.disconnect()?;
+    }
+
+    Ok(())
+}
+
+fn show_interactive(matches: &cli::Cli) -> anyhow::Result<()> {
+    let keys = interactive::KeyMap::load(matches.keys.as_deref())?;
+    let mut connections = Vec::with_capacity(matches.broker.len());
+    // Relays are stopped when dropped at the end
+    let mut relays = Vec::new();
+    for (index, broker) in matches.broker.iter().enumerate() {
+        let (client, connection, server_certificate, relay) = connect(matches, broker, index)?;
+        relays.extend(relay);
+        connections.push(interactive::BrokerConnection {
+            broker: broker.clone(),
+            client,
+            connection,
+            server_certificate,
+        });
+    }
+    let mut clients = connections
+        .iter()
+        .map(|connection| connection.client.clone())
+        .collect::<Vec<_>>();
+    let history_limits = interactive::HistoryLimits {
+        entries_per_topic: matches.history_max_entries,
+        age: matches
+            .history_max_age
+            .map(|seconds| chrono::Duration::seconds(seconds.into())),
+        memory: matches
+            .history_max_memory
+            .map(|mebibytes| mebibytes.saturating_mul(1024 * 1024)),
+    };
+    let auto_expand = interactive::AutoExpand {
+        filters: matches.expand.clone(),
+        max_depth: matches.expand_depth,
+    };
+    let alerting = interactive::Alerting {
+        rules: matches.alert.clone(),
+        bell: matches.alert_bell,
+    };
+    interactive::show(
+        connections,
+        &matches.topic,
+        history_limits,
+        matches.history_dir.as_deref(),
+        &auto_expand,
+        matches.watchlist.as_deref(),
+        &alerting,
+        &keys,
+    )?;
+    for client in &mut clients {
+        client.disconnect()?;
+    }
+    drop(relays);
+    Ok(())
+}
+
+/// Create the client for the broker. The connection is established once it is polled.
+/// The relay for a SOCKS5 proxy or --tls-server-name has to be kept as long as the connection is used.
+fn connect(
+    matches: &cli::Cli,
+    broker: &cli::Broker,
+    index: usize,
+) -> anyhow::Result<(
+    Client,
+    Connection,
+    Option<ServerCertificateArc>,
+    Option<mqtt::proxy::Relay>,
+)> {
+    let (transport, host, port, server_certificate) = match broker {
+        cli::Broker::Tcp { host, port, .. } => (Transport::Tcp, host.clone(), *port, None),
+        #[cfg(feature = "tls")]
+        cli::Broker::Ssl { host, port, .. } => {
+            let (tls, server_certificate) = mqtt::encryption::create_tls_configuration(matches)?;
+            let host = matches
+                .tls_server_name
+                .clone()
+                .unwrap_or_else(|| host.clone());
+            (Transport::Tls(tls), host, *port, Some(server_certificate))
+        }
+        // On WebSockets the whole URL is the host, the port is taken from it
+        #[cfg(feature = "tls")]
+        cli::Broker::WebSocket(url) => (
+            Transport::Ws,
+            url.to_string(),
+            url.port_or_known_default().unwrap_or(80),
+            None,
+        ),
+        #[cfg(feature = "tls")]
+        cli::Broker::WebSocketSsl(url) => {
+            let (tls, server_certificate) = mqtt::encryption::create_tls_configuration(matches)?;
+            let mut url = url.clone();
+            if let Some(server_name) = &matches.tls_server_name {
+                url.set_host(Some(server_name))?;
+            }
+            (
+                Transport::Wss(tls),
+                url.to_string(),
+                url.port_or_known_default().unwrap_or(443),
+                Some(server_certificate),
+            )
+        }
+    };
+
+    // Client ids have to be unique per broker as the same broker might be given multiple times
+    let client_id = match &matches.client_id {
+        Some(client_id) if matches.broker.len() > 1 => format!("{client_id}-{}", index + 1),
+        Some(client_id) => client_id.clone(),
+        None => format!("mqttui-{:x}", rand::random::<u32>()),
+    };
+
+    let mut mqttoptions = MqttOptions::new(client_id, host, port);
+    mqttoptions.set_max_packet_size(usize::MAX, usize::MAX);
+    mqttoptions.set_transport(transport);
+
+    // With another server name rumqttc connects to it, the relay connects to the broker host instead
+    #[cfg(feature = "tls")]
+    let redirect = match (&matches.tls_server_name, broker.tls_host()) {
+        (Some(_), Some(host)) => Some(host),
+        (Some(_), None) => anyhow::bail!(
+            "--tls-server-name can only be used with mqtts:// or wss:// brokers, not {broker}"
+        ),
+        (None, _) => None,
+    };
+    #[cfg(not(feature = "tls"))]
+    let redirect = None;
+
+    let mut relay = None;
+    if let Some(proxy) = &matches.proxy {
+        let (proxy, proxy_relay) = create_proxy(matches, proxy, redirect)?;
+        mqttoptions.set_proxy(proxy);
+        relay = proxy_relay;
+    } else if let Some(redirect) = redirect {
+        let (proxy, redirect_relay) = mqtt::proxy::redirect(redirect)?;
+        mqttoptions.set_proxy(proxy);
+        relay = Some(redirect_relay);
+    }
+
+    #[cfg(feature = "tls")]
+    if !matches.ws_header.is_empty() && broker.is_websocket() {
+        set_ws_headers(&mut mqttoptions, &matches.ws_header)?;
+    }
+
+    if let Some(cli::Credentials { username, password }) = broker.credentials() {
+        mqttoptions.set_credentials(username, password);
+    } else if let Some(password) = &matches.password {
+        let username = matches.username.as_ref().unwrap();
+        mqttoptions.set_credentials(username, password);
+    }
+
+    if let Some(SubCommands::CleanRetained { timeout, .. }) = &matches.subcommands {
+        mqttoptions.set_keep_alive(Duration::from_secs_f32(*timeout));
+    }
+
+    let (client, connection) = Client::new(mqttoptions, 10);
+    Ok((client, connection, server_certificate, relay))
+}
+
+/// Subscribe to the topics and keep track of whether the broker accepts them
+fn subscribe(client: &mut Client, topics: Vec<String>) -> anyhow::Result<SubscriptionTracker> {
+    let mut subscriptions = SubscriptionTracker::new(topics, QoS::AtLeastOnce);
+    subscriptions.resubscribe(client)?;
+    Ok(subscriptions)
+}
+
+/// With `redirect` the tunnel goes to this host instead of the broker host given to rumqttc
+#[cfg_attr(not(feature = "tls"), allow(unused_variables))]
+fn create_proxy(
+    matches: &cli::Cli,
+    proxy: &cli::Proxy,
+    redirect: Option<&str>,
+) -> anyhow::Result<(rumqttc::Proxy, Option<mqtt::proxy::Relay>)> {
+    anyhow::ensure!(
+        redirect.is_none() || matches!(proxy, cli::Proxy::Socks5(_) | cli::Proxy::Socks5h(_)),
+        "--tls-server-name can only be combined with a SOCKS5 proxy"
+    );
+    Ok(match proxy {
+        cli::Proxy::Http(url) => (mqtt::proxy::http(url, ProxyType::Http)?, None),
+        #[cfg(feature = "tls")]
+        cli::Proxy::Https(url) => (
+            mqtt::proxy::http(
+                url,
+                ProxyType::Https(mqtt::encryption::create_proxy_tls_configuration(matches)?),
+            )?,
+            None,
+        ),
+        cli::Proxy::Socks5(url) => {
+            let (proxy, relay) = mqtt::proxy::socks5(url, false, redirect)?;
+            (proxy, Some(relay))
+        }
+        cli::Proxy::Socks5h(url) => {
+            let (proxy, relay) = mqtt::proxy::socks5(url, true, redirect)?;
+            (proxy, Some(relay))
         }
+    })
+}
+
+/// Headers replace the ones set by rumqttc like `Sec-WebSocket-Protocol`
+#[cfg(feature = "tls")]
+fn set_ws_headers(
+    mqttoptions: &mut MqttOptions,
+    ws_header: &[(String, String)],
+) -> anyhow::Result<()> {
+    let mut headers = http::HeaderMap::new();
+    for (name, value) in ws_header {
+        headers.append(
+            http::HeaderName::from_bytes(name.as_bytes())?,
+            http::HeaderValue::from_str(value)?,
+        );
     }
 
+    mqttoptions.set_request_modifier(move |mut request| {
+        let headers = headers.clone();
+        async move {
+            request.headers_mut().extend(headers);
+            request
+        }
+    });
     Ok(())
 }
@@ -0,0 +1,200 @@
+//! Append-only file of received MQTT messages.
+//!
+//! The file starts with [`MAGIC`] followed by the records.
+//! Each record consists of the receive time in milliseconds since the UNIX epoch (`i64`), the [`QoS`] (`u8`), the retain flag (`u8`),
+//! the length of the topic (`u16`) followed by the topic and the length of the payload (`u32`) followed by the payload.
+//! All numbers are big endian.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use chrono::{DateTime, Local, TimeZone};
+use rumqttc::{Publish, QoS};
+
+pub const MAGIC: &[u8] = b"mqttui-capture-1\n";
+
+pub struct Writer {
+    file: BufWriter<File>,
+}
+
+impl Writer {
+    /// Open the file for appending. A new file is created including the header.
+    pub fn append(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(MAGIC)?;
+        }
+        Ok(Self {
+            file: BufWriter::new(file),
+        })
+    }
+
+    /// Buffer the record, it reaches the file on [`Self::flush`] or when the buffer is full
+    pub fn write(&mut self, packet: &Publish, time: DateTime<Local>) -> io::Result<()> {
+        write_record(&mut self.file, packet, time)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Replace the file with one containing only the given records.
+/// A temporary file is renamed so the old records are not lost when this fails.
+pub fn rewrite(path: &Path, records: &[(Publish, DateTime<Local>)]) -> io::Result<()> {
+    let temporary = path.with_extension("tmp");
+    let mut file = BufWriter::new(File::create(&temporary)?);
+    file.write_all(MAGIC)?;
+    for (packet, time) in records {
+        write_record(&mut file, packet, *time)?;
+    }
+    file.flush()?;
+    drop(file);
+    fs::rename(temporary, path)
+}
+
+pub fn write_record(
+    writer: &mut impl Write,
+    packet: &Publish,
+    time: DateTime<Local>,
+) -> io::Result<()> {
+    let topic_length = u16::try_from(packet.topic.len())
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "topic is too long"))?;
+    let payload_length = u32::try_from(packet.payload.len())
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "payload is too long"))?;
+    writer.write_all(&time.timestamp_millis().to_be_bytes())?;
+    writer.write_all(&[packet.qos as u8, u8::from(packet.retain)])?;
+    writer.write_all(&topic_length.to_be_bytes())?;
+    writer.write_all(packet.topic.as_bytes())?;
+    writer.write_all(&payload_length.to_be_bytes())?;
+    writer.write_all(&packet.payload)?;
+    Ok(())
+}
+
+/// Iterates over the records of a capture.
+///
+/// A record cut short at the end, for example when mqttui was killed while writing it, ends the iteration.
+pub struct Reader<R> {
+    reader: R,
+}
+
+impl Reader<BufReader<File>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not a capture of mqttui",
+            ));
+        }
+        Ok(Self { reader })
+    }
+
+    fn read_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buffer = [0; N];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn read_vec(&mut self, length: u32) -> io::Result<Vec<u8>> {
+        // Do not trust the length for the allocation in case the file is corrupted
+        let mut buffer = Vec::new();
+        (&mut self.reader)
+            .take(length.into())
+            .read_to_end(&mut buffer)?;
+        if buffer.len() < length as usize {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        Ok(buffer)
+    }
+
+    fn read_record(&mut self) -> io::Result<(Publish, DateTime<Local>)> {
+        let millis = i64::from_be_bytes(self.read_array()?);
+        let [qos, retain] = self.read_array()?;
+        let topic_length = u16::from_be_bytes(self.read_array()?);
+        let topic = String::from_utf8(self.read_vec(topic_length.into())?)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+        let payload_length = u32::from_be_bytes(self.read_array()?);
+        let payload = self.read_vec(payload_length)?;
+
+        let qos = match qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "invalid QoS")),
+        };
+        let time = Local
+            .timestamp_millis_opt(millis)
+            .single()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "invalid time"))?;
+        let mut packet = Publish::new(topic, qos, payload);
+        packet.retain = retain != 0;
+        Ok((packet, time))
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = io::Result<(Publish, DateTime<Local>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_record() {
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => None,
+            result => Some(result),
+        }
+    }
+}
+
+#[cfg(test)]
+fn capture_of(packets: &[(Publish, DateTime<Local>)]) -> Vec<u8> {
+    let mut capture = MAGIC.to_vec();
+    for (packet, time) in packets {
+        write_record(&mut capture, packet, *time).unwrap();
+    }
+    capture
+}
+
+#[test]
+fn records_survive_roundtrip() {
+    let time = Local.timestamp_millis_opt(1_700_000_000_123).unwrap();
+    let mut retained = Publish::new("foo/bar", QoS::AtLeastOnce, "42");
+    retained.retain = true;
+    let binary = Publish::new("foo", QoS::ExactlyOnce, vec![0xff, 0x00]);
+    let capture = capture_of(&[(retained.clone(), time), (binary.clone(), time)]);
+
+    let actual = Reader::new(capture.as_slice())
+        .unwrap()
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(actual, [(retained, time), (binary, time)]);
+}
+
+#[test]
+fn record_cut_short_is_ignored() {
+    let time = Local::now();
+    let mut capture = capture_of(&[
+        (Publish::new("foo", QoS::AtMostOnce, "1"), time),
+        (Publish::new("foo", QoS::AtMostOnce, "2"), time),
+    ]);
+    capture.pop();
+    let actual = Reader::new(capture.as_slice())
+        .unwrap()
+        .map(|record| record.unwrap().0.payload)
+        .collect::<Vec<_>>();
+    assert_eq!(actual, ["1"]);
+}
+
+#[test]
+fn other_files_are_rejected() {
+    let err = Reader::new(b"time,topic,payload\n12:00,foo,42\n".as_slice())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
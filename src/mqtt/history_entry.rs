@@ -54,15 +54,21 @@ impl Payload {
     }
 }
 
+#[derive(Clone)]
 pub struct HistoryEntry {
     pub qos: QoS,
     pub time: Time,
+    /// Also known for retained messages in contrast to [`Self::time`]
+    pub received: DateTime<Local>,
     pub payload_size: usize,
     pub payload: Payload,
+    /// Payload as received, used to export it unchanged
+    pub raw: bytes::Bytes,
 }
 
 impl HistoryEntry {
     pub fn new(packet: &Publish, time: DateTime<Local>) -> Self {
+        let received = time;
         let time = if packet.retain {
             Time::Retained
         } else {
@@ -71,10 +77,20 @@ impl HistoryEntry {
         Self {
             qos: packet.qos,
             time,
+            received,
             payload_size: packet.payload.len(),
             payload: Payload::new(&packet.payload),
+            // Shares the payload of the packet instead of copying it
+            raw: packet.payload.clone(),
         }
     }
+
+    /// Packet as it was received, for example to persist it again
+    pub fn to_packet(&self, topic: &str) -> Publish {
+        let mut packet = Publish::new(topic, self.qos, self.raw.to_vec());
+        packet.retain = matches!(self.time, Time::Retained);
+        packet
+    }
 }
 
 #[test]
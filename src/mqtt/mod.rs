@@ -1,6 +1,11 @@
+pub mod capture;
 #[cfg(feature = "tls")]
 pub mod encryption;
 mod history_entry;
+pub mod proxy;
+mod server_certificate;
+pub mod subscription;
 pub mod topic;
 
 pub use history_entry::*;
+pub use server_certificate::*;
@@ -0,0 +1,14 @@
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Local};
+
+/// Information about the server certificate which was verified on the last TLS handshake
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "tls"), allow(dead_code))]
+pub struct ServerCertificate {
+    pub subject: String,
+    pub issuer: String,
+    pub not_after: DateTime<Local>,
+}
+
+pub type ServerCertificateArc = Arc<RwLock<Option<ServerCertificate>>>;
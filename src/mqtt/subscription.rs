@@ -0,0 +1,234 @@
+use std::collections::{HashMap, VecDeque};
+
+use rumqttc::{
+    Client, ClientError, Event, Outgoing, Packet, QoS, SubAck, SubscribeFilter, SubscribeReasonCode,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubscriptionState {
+    /// Waiting for the `SubAck` of the broker
+    Pending,
+    /// The broker accepted the subscription with the given [`QoS`]
+    Granted(QoS),
+    /// The broker rejected the subscription
+    Rejected,
+    /// The subscribe request could not be sent
+    Error(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Subscription {
+    pub filter: String,
+    pub qos: QoS,
+    pub state: SubscriptionState,
+}
+
+/// Keeps track of the subscriptions and matches the `SubAck`s of the broker to them.
+///
+/// The eventloop only reports the packet id of a subscribe packet once it was sent.
+/// Sent requests are therefore queued until their `Outgoing::Subscribe` event assigns them their packet id.
+/// To keep the queue in the order of the requests the tracker has to be locked while sending one.
+#[derive(Debug, Default)]
+pub struct SubscriptionTracker {
+    subscriptions: Vec<Subscription>,
+    unsent: VecDeque<Vec<String>>,
+    sent: HashMap<u16, Vec<String>>,
+}
+
+impl SubscriptionTracker {
+    /// Track the given filters which are not yet subscribed, see [`Self::resubscribe`]
+    pub fn new(filters: Vec<String>, qos: QoS) -> Self {
+        let subscriptions = filters
+            .into_iter()
+            .map(|filter| Subscription {
+                filter,
+                qos,
+                state: SubscriptionState::Pending,
+            })
+            .collect();
+        Self {
+            subscriptions,
+            ..Self::default()
+        }
+    }
+
+    pub fn get_subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    /// Filters which were rejected by the broker
+    pub fn get_rejected(&self) -> Vec<&str> {
+        self.subscriptions
+            .iter()
+            .filter(|s| s.state == SubscriptionState::Rejected)
+            .map(|s| s.filter.as_str())
+            .collect()
+    }
+
+    /// Returns an error when the broker rejected any of the subscriptions
+    pub fn ensure_none_rejected(&self) -> anyhow::Result<()> {
+        let rejected = self.get_rejected();
+        anyhow::ensure!(
+            rejected.is_empty(),
+            "The broker rejected the subscription to {}",
+            rejected.join(", ")
+        );
+        Ok(())
+    }
+
+    /// Subscribe to the filter or update its [`QoS`] when it is already subscribed
+    pub fn subscribe(
+        &mut self,
+        client: &mut Client,
+        filter: String,
+        qos: QoS,
+    ) -> Result<(), ClientError> {
+        let result = client.try_subscribe(&filter, qos);
+        let state = match &result {
+            Ok(()) => {
+                self.unsent.push_back(vec![filter.clone()]);
+                SubscriptionState::Pending
+            }
+            Err(err) => SubscriptionState::Error(err.to_string()),
+        };
+
+        if let Some(existing) = self.subscriptions.iter_mut().find(|s| s.filter == filter) {
+            existing.qos = qos;
+            existing.state = state;
+        } else {
+            self.subscriptions.push(Subscription { filter, qos, state });
+        }
+        result
+    }
+
+    pub fn unsubscribe(&mut self, client: &mut Client, filter: &str) -> Result<(), ClientError> {
+        client.try_unsubscribe(filter)?;
+        self.subscriptions.retain(|s| s.filter != filter);
+        Ok(())
+    }
+
+    /// Subscribe to all known filters again, for example after a reconnect
+    pub fn resubscribe(&mut self, client: &mut Client) -> Result<(), ClientError> {
+        self.unsent.clear();
+        self.sent.clear();
+        if self.subscriptions.is_empty() {
+            return Ok(());
+        }
+
+        let filters = self
+            .subscriptions
+            .iter()
+            .map(|s| SubscribeFilter::new(s.filter.clone(), s.qos))
+            .collect::<Vec<_>>();
+        let result = client.try_subscribe_many(filters);
+        for subscription in &mut self.subscriptions {
+            subscription.state = match &result {
+                Ok(()) => SubscriptionState::Pending,
+                Err(err) => SubscriptionState::Error(err.to_string()),
+            };
+        }
+        if result.is_ok() {
+            self.unsent.push_back(
+                self.subscriptions
+                    .iter()
+                    .map(|s| s.filter.clone())
+                    .collect(),
+            );
+        }
+        result
+    }
+
+    /// Update the subscription states based on the events of the connection
+    pub fn on_event(&mut self, event: &Event) {
+        match event {
+            Event::Outgoing(Outgoing::Subscribe(pkid)) => {
+                if let Some(filters) = self.unsent.pop_front() {
+                    self.sent.insert(*pkid, filters);
+                }
+            }
+            Event::Incoming(Packet::SubAck(suback)) => self.on_suback(suback),
+            _ => {}
+        }
+    }
+
+    fn on_suback(&mut self, suback: &SubAck) {
+        let filters = self.sent.remove(&suback.pkid).unwrap_or_default();
+        for (filter, code) in filters.iter().zip(&suback.return_codes) {
+            if let Some(subscription) = self.subscriptions.iter_mut().find(|s| &s.filter == filter)
+            {
+                subscription.state = match code {
+                    SubscribeReasonCode::Success(qos) => SubscriptionState::Granted(*qos),
+                    SubscribeReasonCode::Failure => SubscriptionState::Rejected,
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn tracker_with(filters: &[&str]) -> SubscriptionTracker {
+    let filters = filters.iter().map(|f| (*f).to_string()).collect::<Vec<_>>();
+    let mut tracker = SubscriptionTracker::new(filters.clone(), QoS::AtLeastOnce);
+    // Pretend the subscribe request was sent
+    tracker.unsent.push_back(filters);
+    tracker
+}
+
+#[test]
+fn suback_is_matched_by_packet_id() {
+    let mut tracker = tracker_with(&["foo/#", "bar"]);
+    tracker.on_event(&Event::Outgoing(Outgoing::Subscribe(42)));
+    tracker.on_event(&Event::Incoming(Packet::SubAck(SubAck::new(
+        42,
+        vec![
+            SubscribeReasonCode::Success(QoS::AtMostOnce),
+            SubscribeReasonCode::Failure,
+        ],
+    ))));
+    let states = tracker
+        .get_subscriptions()
+        .iter()
+        .map(|s| s.state.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        states,
+        [
+            SubscriptionState::Granted(QoS::AtMostOnce),
+            SubscriptionState::Rejected
+        ]
+    );
+}
+
+#[test]
+fn suback_of_unknown_packet_id_is_ignored() {
+    let mut tracker = tracker_with(&["foo"]);
+    tracker.on_event(&Event::Outgoing(Outgoing::Subscribe(1)));
+    tracker.on_event(&Event::Incoming(Packet::SubAck(SubAck::new(
+        2,
+        vec![SubscribeReasonCode::Failure],
+    ))));
+    assert_eq!(
+        tracker.get_subscriptions()[0].state,
+        SubscriptionState::Pending
+    );
+}
+
+#[test]
+fn rejected_subscription_is_an_error() {
+    let mut tracker = tracker_with(&["foo", "secret/#"]);
+    assert!(tracker.ensure_none_rejected().is_ok());
+    tracker.on_event(&Event::Outgoing(Outgoing::Subscribe(1)));
+    tracker.on_event(&Event::Incoming(Packet::SubAck(SubAck::new(
+        1,
+        vec![
+            SubscribeReasonCode::Success(QoS::AtLeastOnce),
+            SubscribeReasonCode::Failure,
+        ],
+    ))));
+    assert_eq!(tracker.get_rejected(), ["secret/#"]);
+    let err = tracker.ensure_none_rejected().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "The broker rejected the subscription to secret/#"
+    );
+}
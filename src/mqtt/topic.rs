@@ -1,7 +1,81 @@
+use std::collections::HashSet;
+
 pub fn get_parent(topic: &str) -> Option<&str> {
     topic.rfind('/').map(|i| &topic[0..i])
 }
 
+/// Whether the topic is somewhere below the parent
+pub fn is_below(topic: &str, parent: &str) -> bool {
+    topic
+        .strip_prefix(parent)
+        .map_or(false, |rest| rest.starts_with('/'))
+}
+
+/// MQTT topic filter matching where `+` matches one level and `#` all remaining levels
+pub fn topic_filter_matches(filter: &str, topic: &str) -> bool {
+    let mut levels = topic.split('/');
+    for filter_level in filter.split('/') {
+        match filter_level {
+            "#" => return true,
+            "+" => {
+                if levels.next().is_none() {
+                    return false;
+                }
+            }
+            _ => {
+                if levels.next() != Some(filter_level) {
+                    return false;
+                }
+            }
+        }
+    }
+    levels.next().is_none()
+}
+
+/// Topics like search hits which are shown with the topics above and below them.
+/// The topics above are collected once as checking every topic of a big tree against every hit is slow.
+#[derive(Debug, Default)]
+pub struct TopicSet {
+    topics: HashSet<String>,
+    above: HashSet<String>,
+}
+
+impl TopicSet {
+    pub fn new(topics: HashSet<String>) -> Self {
+        let above = topics
+            .iter()
+            .flat_map(|topic| std::iter::successors(get_parent(topic), |topic| get_parent(topic)))
+            .map(ToString::to_string)
+            .collect();
+        Self { topics, above }
+    }
+
+    pub fn len(&self) -> usize {
+        self.topics.len()
+    }
+
+    pub fn contains(&self, topic: &str) -> bool {
+        self.topics.contains(topic)
+    }
+
+    /// Topics above the contained ones need to be opened to show them
+    pub fn is_above(&self, topic: &str) -> bool {
+        self.above.contains(topic)
+    }
+
+    pub fn get_above(&self) -> impl Iterator<Item = &str> {
+        self.above.iter().map(String::as_str)
+    }
+
+    /// The contained topics with the topics above and below them
+    pub fn shows(&self, topic: &str) -> bool {
+        self.contains(topic)
+            || self.is_above(topic)
+            || std::iter::successors(get_parent(topic), |topic| get_parent(topic))
+                .any(|parent| self.contains(parent))
+    }
+}
+
 #[test]
 fn parent_works() {
     assert_eq!(None, get_parent("a"));
@@ -9,3 +83,38 @@ fn parent_works() {
     assert_eq!(Some("a/b"), get_parent("a/b/c"));
     assert_eq!(Some("a/b/c"), get_parent("a/b/c/d"));
 }
+
+#[test]
+fn is_below_works() {
+    assert!(is_below("a/b", "a"));
+    assert!(is_below("a/b/c", "a"));
+    assert!(!is_below("a", "a"));
+    assert!(!is_below("ab/c", "a"));
+    assert!(!is_below("a", "a/b"));
+}
+
+#[test]
+fn topic_filter_works() {
+    assert!(topic_filter_matches("foo/+/bar", "foo/a/bar"));
+    assert!(!topic_filter_matches("foo/+/bar", "foo/a/b/bar"));
+    assert!(!topic_filter_matches("foo/+", "foo"));
+    assert!(topic_filter_matches("foo/#", "foo"));
+    assert!(topic_filter_matches("foo/#", "foo/a/b"));
+    assert!(topic_filter_matches("#", "foo/a"));
+    assert!(!topic_filter_matches("foo/bar", "foo/bar/baz"));
+    assert!(!topic_filter_matches("foo/bar", "foo"));
+}
+
+#[test]
+fn topic_set_shows_above_and_below() {
+    let set = TopicSet::new(HashSet::from(["a/b".to_string(), "c".to_string()]));
+    assert_eq!(set.len(), 2);
+    assert!(set.contains("a/b"));
+    assert!(set.is_above("a"));
+    assert!(!set.is_above("a/b"));
+    assert!(set.shows("a"));
+    assert!(set.shows("a/b/c"));
+    assert!(set.shows("c/d"));
+    assert!(!set.shows("a/c"));
+    assert!(!set.shows("ab"));
+}
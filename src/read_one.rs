@@ -4,6 +4,7 @@ use std::time::Duration;
 
 use rumqttc::{Client, Connection};
 
+use crate::mqtt::subscription::SubscriptionTracker;
 use crate::mqtt::Payload;
 
 enum Finished {
@@ -12,16 +13,25 @@ enum Finished {
     NonUtf8,
 }
 
-pub fn show(mut client: Client, mut connection: Connection, ignore_retained: bool) {
+pub fn show(
+    mut client: Client,
+    mut connection: Connection,
+    mut subscriptions: SubscriptionTracker,
+    ignore_retained: bool,
+) -> anyhow::Result<()> {
     for notification in connection.iter() {
-        if let rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) =
-            notification.expect("connection error")
-        {
+        let notification = notification.expect("connection error");
+        subscriptions.on_event(&notification);
+        if let rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) = notification {
             break;
         }
     }
     let mut done = Finished::StillWaiting;
     for notification in connection.iter() {
+        if let Ok(event) = &notification {
+            subscriptions.on_event(event);
+            subscriptions.ensure_none_rejected()?;
+        }
         match notification {
             Ok(rumqttc::Event::Outgoing(outgoing)) => {
                 if outgoing == rumqttc::Outgoing::Disconnect {
@@ -66,4 +76,5 @@ pub fn show(mut client: Client, mut connection: Connection, ignore_retained: boo
     if matches!(done, Finished::NonUtf8) {
         exit(1);
     }
+    Ok(())
 }